@@ -1,5 +1,8 @@
+use serde::Deserialize;
+use std::{fs, path::Path};
 use termion::color::Rgb;
 
+/// The palette the built-in themes are derived from.
 pub struct Color {
     pub text: Rgb,
     pub teal: Rgb,
@@ -7,76 +10,613 @@ pub struct Color {
     pub peach: Rgb,
     pub red: Rgb,
     pub green: Rgb,
+    pub mauve: Rgb,
+    pub overlay: Rgb,
+    pub mantle: Rgb,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxColors {
+    pub text: Rgb,
+    pub keyword: Rgb,
+    pub string: Rgb,
+    pub number: Rgb,
+    pub comment: Rgb,
+    pub function: Rgb,
+    pub type_name: Rgb,
+}
+
+impl SyntaxColors {
+    /// The syntax colours derived from the main colours of a theme.
+    fn derive(
+        text: Rgb,
+        primary: Rgb,
+        secondary: Rgb,
+        tertiary: Rgb,
+        line_number: Rgb,
+        quote: Rgb,
+        warning: Rgb,
+    ) -> SyntaxColors {
+        SyntaxColors {
+            text,
+            keyword: quote,
+            string: tertiary,
+            number: warning,
+            comment: line_number,
+            function: secondary,
+            type_name: primary,
+        }
+    }
+}
+
+/// How headings of one level are rendered.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeadingStyle {
+    pub color: Rgb,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+}
+
+impl HeadingStyle {
+    fn bold(color: Rgb) -> HeadingStyle {
+        HeadingStyle {
+            color,
+            bold: true,
+            italic: false,
+            underline: false,
+        }
+    }
+
+    /// The default styles of the levels 1 to 6.
+    fn defaults(
+        primary: Rgb,
+        secondary: Rgb,
+        tertiary: Rgb,
+        accent: Rgb,
+        text: Rgb,
+    ) -> [HeadingStyle; 6] {
+        [
+            HeadingStyle::bold(primary),
+            HeadingStyle::bold(secondary),
+            HeadingStyle::bold(tertiary),
+            HeadingStyle::bold(accent),
+            HeadingStyle::bold(text),
+            HeadingStyle {
+                italic: true,
+                ..HeadingStyle::bold(text)
+            },
+        ]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct ThemeColors {
     pub text: Rgb,
     pub primary: Rgb,
     pub secondary: Rgb,
     pub tertiary: Rgb,
     pub accent: Rgb,
+    pub bullet: Rgb,
+    pub list_number: Rgb,
+    pub code_background: Rgb,
+    pub line_number: Rgb,
+    pub quote: Rgb,
+    /// The colours of `> [!NOTE]` style callouts.
+    pub note: Rgb,
+    pub tip: Rgb,
+    pub important: Rgb,
+    pub warning: Rgb,
+    pub caution: Rgb,
+    /// The styles of the heading levels 1 to 6.
+    pub headings: [HeadingStyle; 6],
+    /// `None` keeps the background of the terminal.
+    pub background: Option<Rgb>,
+    pub syntax: SyntaxColors,
 }
 
-pub enum Theme {
-    CatppuccinLatte,
-    CatppuccinMocha,
-    OneDark,
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    name: String,
+    colors: ThemeColors,
 }
 
 impl Theme {
-    fn get_colors(&self) -> Color {
-        match self {
-            Theme::CatppuccinLatte => Color {
-                text: hex_to_rgb("#4c4f69"),
-                teal: hex_to_rgb("#179299"),
-                sky: hex_to_rgb("#04a5e5"),
-                peach: hex_to_rgb("#fe640b"),
-                red: hex_to_rgb("#d20f39"),
-                green: hex_to_rgb("#40a02b"),
-            },
-            Theme::CatppuccinMocha => Color {
-                text: hex_to_rgb("#cdd6f4"),
-                teal: hex_to_rgb("#94e2d5"),
-                sky: hex_to_rgb("#94e2d5"),
-                peach: hex_to_rgb("#fab387"),
-                red: hex_to_rgb("#f38ba8"),
-                green: hex_to_rgb("#a6e3a1"),
-            },
-            Theme::OneDark => Color {
-                text: hex_to_rgb("#abb2bf"),
-                teal: hex_to_rgb("#56b6c2"),
-                sky: hex_to_rgb("#61afef"),
-                peach: hex_to_rgb("#e5c07b"),
-                red: hex_to_rgb("#e06c75"),
-                green: hex_to_rgb("#98c379"),
+    pub fn built_in() -> Vec<Theme> {
+        vec![
+            Theme::from_palette(
+                "Catppuccin Latte",
+                Color {
+                    text: hex_to_rgb("#4c4f69"),
+                    teal: hex_to_rgb("#179299"),
+                    sky: hex_to_rgb("#04a5e5"),
+                    peach: hex_to_rgb("#fe640b"),
+                    red: hex_to_rgb("#d20f39"),
+                    green: hex_to_rgb("#40a02b"),
+                    mauve: hex_to_rgb("#8839ef"),
+                    overlay: hex_to_rgb("#9ca0b0"),
+                    mantle: hex_to_rgb("#e6e9ef"),
+                },
+            ),
+            Theme::from_palette(
+                "Catppuccin Mocha",
+                Color {
+                    text: hex_to_rgb("#cdd6f4"),
+                    teal: hex_to_rgb("#94e2d5"),
+                    sky: hex_to_rgb("#94e2d5"),
+                    peach: hex_to_rgb("#fab387"),
+                    red: hex_to_rgb("#f38ba8"),
+                    green: hex_to_rgb("#a6e3a1"),
+                    mauve: hex_to_rgb("#cba6f7"),
+                    overlay: hex_to_rgb("#6c7086"),
+                    mantle: hex_to_rgb("#181825"),
+                },
+            ),
+            Theme::from_palette(
+                "One Dark",
+                Color {
+                    text: hex_to_rgb("#abb2bf"),
+                    teal: hex_to_rgb("#56b6c2"),
+                    sky: hex_to_rgb("#61afef"),
+                    peach: hex_to_rgb("#e5c07b"),
+                    red: hex_to_rgb("#e06c75"),
+                    green: hex_to_rgb("#98c379"),
+                    mauve: hex_to_rgb("#c678dd"),
+                    overlay: hex_to_rgb("#5c6370"),
+                    mantle: hex_to_rgb("#21252b"),
+                },
+            ),
+        ]
+    }
+
+    fn from_palette(name: &str, colors: Color) -> Theme {
+        Theme {
+            name: String::from(name),
+            colors: ThemeColors {
+                text: colors.text,
+                primary: colors.teal,
+                secondary: colors.sky,
+                tertiary: colors.green,
+                accent: colors.green,
+                bullet: colors.teal,
+                list_number: colors.sky,
+                code_background: colors.mantle,
+                line_number: colors.overlay,
+                quote: colors.mauve,
+                note: colors.sky,
+                tip: colors.green,
+                important: colors.mauve,
+                warning: colors.peach,
+                caution: colors.red,
+                headings: HeadingStyle::defaults(
+                    colors.teal,
+                    colors.sky,
+                    colors.green,
+                    colors.green,
+                    colors.text,
+                ),
+                background: None,
+                syntax: SyntaxColors::derive(
+                    colors.text,
+                    colors.teal,
+                    colors.sky,
+                    colors.green,
+                    colors.overlay,
+                    colors.mauve,
+                    colors.peach,
+                ),
             },
         }
     }
 
-    pub fn get_theme_colors(&self) -> ThemeColors {
-        let colors = self.get_colors();
-        ThemeColors {
-            text: colors.text,
-            primary: colors.teal,
-            secondary: colors.sky,
-            tertiary: colors.green,
-            accent: colors.green,
+    /// Loads a theme from a TOML file. A theme can `extends` any of `themes`
+    /// and then only needs to list the colours it changes.
+    pub fn load(path: &Path, themes: &[Theme]) -> Result<Theme, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|err| format!("Error reading theme {}: {}", path.display(), err))?;
+        Theme::parse(&content, themes)
+            .map_err(|err| format!("Error in theme {}: {}", path.display(), err))
+    }
+
+    fn parse(content: &str, themes: &[Theme]) -> Result<Theme, String> {
+        let file: ThemeFile = toml::from_str(content).map_err(|err| err.to_string())?;
+        let base = match &file.extends {
+            Some(name) => Some(
+                themes
+                    .iter()
+                    .find(|theme| theme.name == *name)
+                    .map(|theme| &theme.colors)
+                    .ok_or_else(|| format!("unknown theme `{}` in `extends`", name))?,
+            ),
+            None => None,
+        };
+        macro_rules! color {
+            (colors . $field:ident, $fallback:expr) => {
+                match (&file.colors.$field, base) {
+                    (Some(color), _) => color.0,
+                    (None, Some(base)) => base.$field,
+                    (None, None) => $fallback,
+                }
+            };
+        }
+        let missing = |field: &str| {
+            format!(
+                "missing colour `{}`, set it or inherit it with `extends`",
+                field
+            )
+        };
+        let text = color!(colors.text, return Err(missing("text")));
+        let primary = color!(colors.primary, return Err(missing("primary")));
+        let secondary = color!(colors.secondary, return Err(missing("secondary")));
+        let tertiary = color!(colors.tertiary, return Err(missing("tertiary")));
+        let accent = color!(colors.accent, return Err(missing("accent")));
+        let line_number = color!(colors.line_number, return Err(missing("line_number")));
+        let quote = color!(colors.quote, return Err(missing("quote")));
+        let warning = color!(colors.warning, accent);
+        // Headings and syntax colours follow the merged colours, except where
+        // the base theme set them itself.
+        let derived_headings = HeadingStyle::defaults(primary, secondary, tertiary, accent, text);
+        let default_headings: [HeadingStyle; 6] = match base {
+            Some(base) => {
+                let base_defaults = HeadingStyle::defaults(
+                    base.primary,
+                    base.secondary,
+                    base.tertiary,
+                    base.accent,
+                    base.text,
+                );
+                std::array::from_fn(|i| HeadingStyle {
+                    color: if base.headings[i].color == base_defaults[i].color {
+                        derived_headings[i].color
+                    } else {
+                        base.headings[i].color
+                    },
+                    ..base.headings[i]
+                })
+            }
+            None => derived_headings,
+        };
+        let derived_syntax = SyntaxColors::derive(
+            text,
+            primary,
+            secondary,
+            tertiary,
+            line_number,
+            quote,
+            warning,
+        );
+        let base_syntax = base.map(|base| {
+            SyntaxColors::derive(
+                base.text,
+                base.primary,
+                base.secondary,
+                base.tertiary,
+                base.line_number,
+                base.quote,
+                base.warning,
+            )
+        });
+        macro_rules! syntax {
+            ($field:ident) => {
+                match (&file.syntax.$field, base, &base_syntax) {
+                    (Some(color), _, _) => color.0,
+                    (None, Some(base), Some(base_syntax))
+                        if base.syntax.$field != base_syntax.$field =>
+                    {
+                        base.syntax.$field
+                    }
+                    _ => derived_syntax.$field,
+                }
+            };
         }
+        let heading_files = [
+            &file.headings.h1,
+            &file.headings.h2,
+            &file.headings.h3,
+            &file.headings.h4,
+            &file.headings.h5,
+            &file.headings.h6,
+        ];
+        let headings = std::array::from_fn(|i| {
+            let default = default_headings[i];
+            match heading_files[i] {
+                Some(heading) => HeadingStyle {
+                    color: heading
+                        .color
+                        .as_ref()
+                        .map_or(default.color, |color| color.0),
+                    bold: heading.bold.unwrap_or(default.bold),
+                    italic: heading.italic.unwrap_or(default.italic),
+                    underline: heading.underline.unwrap_or(default.underline),
+                },
+                None => default,
+            }
+        });
+        let colors = ThemeColors {
+            text,
+            primary,
+            secondary,
+            tertiary,
+            accent,
+            bullet: color!(colors.bullet, return Err(missing("bullet"))),
+            list_number: color!(colors.list_number, return Err(missing("list_number"))),
+            code_background: color!(
+                colors.code_background,
+                return Err(missing("code_background"))
+            ),
+            line_number,
+            quote,
+            // Callouts fall back to the main colours so that theme files
+            // written before they existed still load.
+            note: color!(colors.note, secondary),
+            tip: color!(colors.tip, tertiary),
+            important: color!(colors.important, quote),
+            warning,
+            caution: color!(colors.caution, primary),
+            headings,
+            background: match &file.colors.background {
+                Some(color) => Some(color.0),
+                None => base.and_then(|base| base.background),
+            },
+            syntax: SyntaxColors {
+                text: syntax!(text),
+                keyword: syntax!(keyword),
+                string: syntax!(string),
+                number: syntax!(number),
+                comment: syntax!(comment),
+                function: syntax!(function),
+                type_name: syntax!(type_name),
+            },
+        };
+        Ok(Theme {
+            name: file.name,
+            colors,
+        })
+    }
+
+    pub fn get_theme_colors(&self) -> &ThemeColors {
+        &self.colors
     }
 
     pub fn get_name(&self) -> &str {
-        match self {
-            Theme::CatppuccinLatte => "Catppuccin Latte",
-            Theme::CatppuccinMocha => "Catppuccin Mocha",
-            Theme::OneDark => "One Dark",
+        &self.name
+    }
+}
+
+/// Loads every `*.toml` file in `directory` as a theme, in the order of their
+/// file names so themes can extend the ones before them. A missing directory
+/// contributes no themes.
+pub fn load_theme_directory(directory: &Path, themes: &mut Vec<Theme>) -> Result<(), String> {
+    let Ok(entries) = fs::read_dir(directory) else {
+        return Ok(());
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "toml")
+        })
+        .collect();
+    paths.sort();
+    for path in paths {
+        let theme = Theme::load(&path, themes)?;
+        add_theme(themes, theme);
+    }
+    Ok(())
+}
+
+/// Adds `theme`, replacing a theme with the same name. Returns its index.
+pub fn add_theme(themes: &mut Vec<Theme>, theme: Theme) -> usize {
+    match themes
+        .iter()
+        .position(|existing| existing.name == theme.name)
+    {
+        Some(index) => {
+            themes[index] = theme;
+            index
+        }
+        None => {
+            themes.push(theme);
+            themes.len() - 1
         }
     }
 }
 
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThemeFile {
+    name: String,
+    extends: Option<String>,
+    #[serde(default)]
+    colors: ThemeFileColors,
+    #[serde(default)]
+    syntax: ThemeFileSyntax,
+    #[serde(default)]
+    headings: ThemeFileHeadings,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct ThemeFileColors {
+    text: Option<HexColor>,
+    primary: Option<HexColor>,
+    secondary: Option<HexColor>,
+    tertiary: Option<HexColor>,
+    accent: Option<HexColor>,
+    bullet: Option<HexColor>,
+    list_number: Option<HexColor>,
+    code_background: Option<HexColor>,
+    line_number: Option<HexColor>,
+    quote: Option<HexColor>,
+    note: Option<HexColor>,
+    tip: Option<HexColor>,
+    important: Option<HexColor>,
+    warning: Option<HexColor>,
+    caution: Option<HexColor>,
+    background: Option<HexColor>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct ThemeFileSyntax {
+    text: Option<HexColor>,
+    keyword: Option<HexColor>,
+    string: Option<HexColor>,
+    number: Option<HexColor>,
+    comment: Option<HexColor>,
+    function: Option<HexColor>,
+    #[serde(rename = "type")]
+    type_name: Option<HexColor>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct ThemeFileHeadings {
+    h1: Option<ThemeFileHeading>,
+    h2: Option<ThemeFileHeading>,
+    h3: Option<ThemeFileHeading>,
+    h4: Option<ThemeFileHeading>,
+    h5: Option<ThemeFileHeading>,
+    h6: Option<ThemeFileHeading>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThemeFileHeading {
+    color: Option<HexColor>,
+    bold: Option<bool>,
+    italic: Option<bool>,
+    underline: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(try_from = "String")]
+struct HexColor(Rgb);
+
+impl TryFrom<String> for HexColor {
+    type Error = String;
+
+    fn try_from(hex: String) -> Result<Self, Self::Error> {
+        parse_hex(&hex)
+            .map(HexColor)
+            .ok_or_else(|| format!("invalid colour `{}`, expected #rrggbb", hex))
+    }
+}
+
+/// Parses a `#rrggbb` colour.
+pub fn parse_hex(hex: &str) -> Option<Rgb> {
+    let digits = hex.strip_prefix('#')?;
+    if digits.len() != 6 || !digits.is_ascii() {
+        return None;
+    }
+    let r = u8::from_str_radix(&digits[0..2], 16).ok()?;
+    let g = u8::from_str_radix(&digits[2..4], 16).ok()?;
+    let b = u8::from_str_radix(&digits[4..6], 16).ok()?;
+    Some(Rgb(r, g, b))
+}
+
 fn hex_to_rgb(hex: &str) -> Rgb {
-    let r = u8::from_str_radix(&hex[1..3], 16).unwrap();
-    let g = u8::from_str_radix(&hex[3..5], 16).unwrap();
-    let b = u8::from_str_radix(&hex[5..7], 16).unwrap();
+    parse_hex(hex).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_theme_extending_built_in() {
+        let themes = Theme::built_in();
+        let theme = Theme::parse(
+            "name = \"Mine\"\nextends = \"One Dark\"\n[colors]\nprimary = \"#010203\"\n[syntax]\ntype = \"#0a0b0c\"",
+            &themes,
+        )
+        .unwrap();
+        let base = themes[2].get_theme_colors();
+        assert_eq!(theme.get_name(), "Mine");
+        assert_eq!(theme.get_theme_colors().primary, Rgb(1, 2, 3));
+        assert_eq!(theme.get_theme_colors().syntax.type_name, Rgb(10, 11, 12));
+        assert_eq!(theme.get_theme_colors().text, base.text);
+        assert_eq!(theme.get_theme_colors().syntax.keyword, base.syntax.keyword);
+    }
+
+    #[test]
+    fn test_parse_theme_requires_all_colours_without_base() {
+        let err = Theme::parse("name = \"Mine\"\n[colors]\ntext = \"#ffffff\"", &[]).unwrap_err();
+        assert!(err.contains("`primary`"), "{}", err);
+    }
 
-    Rgb(r, g, b)
+    #[test]
+    fn test_parse_theme_rejects_invalid_colours() {
+        let err = Theme::parse(
+            "name = \"Mine\"\nextends = \"One Dark\"\n[colors]\ntext = \"red\"",
+            &Theme::built_in(),
+        )
+        .unwrap_err();
+        assert!(err.contains("invalid colour `red`"), "{}", err);
+    }
+
+    #[test]
+    fn test_add_theme_replaces_theme_with_same_name() {
+        let mut themes = Theme::built_in();
+        let mut theme = themes[1].clone();
+        theme.colors.background = Some(Rgb(0, 0, 0));
+        assert_eq!(add_theme(&mut themes, theme.clone()), 1);
+        assert_eq!(themes.len(), 3);
+        assert_eq!(themes[1], theme);
+    }
+
+    #[test]
+    fn test_parse_heading_styles() {
+        let themes = Theme::built_in();
+        let theme = Theme::parse(
+            "name = \"Mine\"\nextends = \"One Dark\"\n[colors]\nprimary = \"#ff0000\"\n[headings.h5]\ncolor = \"#010203\"\nunderline = true",
+            &themes,
+        )
+        .unwrap();
+        let headings = &theme.get_theme_colors().headings;
+        assert_eq!(
+            headings[4],
+            HeadingStyle {
+                color: Rgb(1, 2, 3),
+                bold: true,
+                italic: false,
+                underline: true,
+            }
+        );
+        assert_eq!(headings[0], HeadingStyle::bold(Rgb(255, 0, 0)));
+        assert_eq!(headings[1], themes[2].get_theme_colors().headings[1]);
+    }
+
+    #[test]
+    fn test_extended_theme_derives_from_merged_colours() {
+        let themes = Theme::built_in();
+        let theme = Theme::parse(
+            "name = \"Mine\"\nextends = \"One Dark\"\n[colors]\nprimary = \"#ff0000\"",
+            &themes,
+        )
+        .unwrap();
+        let colors = theme.get_theme_colors();
+        assert_eq!(colors.headings[0].color, Rgb(255, 0, 0));
+        assert_eq!(colors.syntax.type_name, Rgb(255, 0, 0));
+        assert_eq!(
+            colors.syntax.keyword,
+            themes[2].get_theme_colors().syntax.keyword
+        );
+
+        // Colours the base theme set itself are inherited as they are.
+        let mut themes = themes;
+        let base = Theme::parse(
+            "name = \"Base\"\nextends = \"One Dark\"\n[syntax]\ntype = \"#010203\"\n[headings.h1]\ncolor = \"#040506\"",
+            &themes,
+        )
+        .unwrap();
+        add_theme(&mut themes, base);
+        let theme = Theme::parse(
+            "name = \"Mine\"\nextends = \"Base\"\n[colors]\nprimary = \"#ff0000\"",
+            &themes,
+        )
+        .unwrap();
+        assert_eq!(theme.get_theme_colors().syntax.type_name, Rgb(1, 2, 3));
+        assert_eq!(theme.get_theme_colors().headings[0].color, Rgb(4, 5, 6));
+    }
 }
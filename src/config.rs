@@ -0,0 +1,11 @@
+use std::{env, path::PathBuf};
+
+/// The directory with the user's themes and settings, following the XDG base
+/// directory spec: `$XDG_CONFIG_HOME/term_deck` or `~/.config/term_deck`.
+pub fn config_dir() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("term_deck"))
+}
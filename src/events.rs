@@ -0,0 +1,90 @@
+use std::{
+    fs,
+    io::stdin,
+    path::PathBuf,
+    thread,
+    time::{Duration, SystemTime},
+};
+use termion::{event::Key, input::TermRead};
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::mpsc::UnboundedSender,
+};
+
+/// Everything the main loop reacts to.
+#[derive(Debug)]
+pub enum Event {
+    Key(Key),
+    /// Another view of the deck moved to a slide and reveal step.
+    Goto {
+        slide: usize,
+        step: usize,
+        /// The connection the move came in on.
+        peer: usize,
+    },
+    /// Another view of the deck connected and needs the current position.
+    PeerConnected,
+    /// The presentation file was modified.
+    FileChanged,
+    /// The terminal window was resized.
+    Resize,
+    Tick,
+}
+
+/// Reads keys from stdin on a separate thread, as termion only offers a
+/// blocking iterator.
+pub fn spawn_key_reader(events: UnboundedSender<Event>) {
+    thread::spawn(move || {
+        for key in stdin().keys().map_while(Result::ok) {
+            if events.send(Event::Key(key)).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+pub fn spawn_ticker(events: UnboundedSender<Event>, interval: Duration) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            if events.send(Event::Tick).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Polls the modification time of `path`, which keeps working when editors
+/// replace the file instead of writing to it.
+pub fn spawn_file_watcher(path: PathBuf, events: UnboundedSender<Event>, interval: Duration) {
+    let modified =
+        |path: &PathBuf| -> Option<SystemTime> { fs::metadata(path).ok()?.modified().ok() };
+    tokio::spawn(async move {
+        let mut last_modified = modified(&path);
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            let current = modified(&path);
+            if current.is_some() && current != last_modified {
+                last_modified = current;
+                if events.send(Event::FileChanged).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+}
+
+pub fn spawn_resize_listener(events: UnboundedSender<Event>) {
+    tokio::spawn(async move {
+        let Ok(mut window_changes) = signal(SignalKind::window_change()) else {
+            return;
+        };
+        while window_changes.recv().await.is_some() {
+            if events.send(Event::Resize).is_err() {
+                break;
+            }
+        }
+    });
+}
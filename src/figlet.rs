@@ -0,0 +1,242 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use crate::text::display_width;
+
+const BUNDLED: [(&str, &str); 2] = [
+    ("compact", include_str!("../fonts/compact.flf")),
+    ("block", include_str!("../fonts/block.flf")),
+];
+
+/// The characters every FIGlet font defines after the printable ASCII ones.
+const GERMAN_CHARACTERS: [u32; 7] = [196, 214, 220, 228, 246, 252, 223];
+
+/// A FIGlet font for rendering text in large letters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Font {
+    height: usize,
+    hardblank: char,
+    /// Whether characters are moved together until they touch. Fonts asking
+    /// for smushing are kerned as well.
+    kerning: bool,
+    glyphs: HashMap<char, Vec<String>>,
+}
+
+impl Font {
+    /// The font bundled with Term Deck by `name`.
+    pub fn bundled(name: &str) -> Option<Font> {
+        BUNDLED
+            .iter()
+            .find(|(bundled, _)| *bundled == name)
+            .map(|(_, content)| Font::parse(content).expect("bundled fonts are valid"))
+    }
+
+    /// Loads a `.flf` file.
+    pub fn load(path: &Path) -> Result<Font, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|err| format!("Error reading font {}: {}", path.display(), err))?;
+        Font::parse(&content).map_err(|err| format!("Error in font {}: {}", path.display(), err))
+    }
+
+    fn parse(content: &str) -> Result<Font, String> {
+        let mut lines = content.lines().enumerate();
+        let (_, header) = lines.next().ok_or("the font is empty")?;
+        let header = header
+            .strip_prefix("flf2a")
+            .ok_or("not a FIGlet font, the first line must start with `flf2a`")?;
+        let hardblank = header.chars().next().ok_or("the hardblank is missing")?;
+        let fields = header[hardblank.len_utf8()..]
+            .split_whitespace()
+            .map(|field| field.parse::<i64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| "invalid numbers in the first line")?;
+        let [height, _baseline, _max_length, old_layout, comment_lines, ..] = fields[..] else {
+            return Err(String::from("the first line needs at least five numbers"));
+        };
+        if height < 1 {
+            return Err(String::from("the height must be at least 1"));
+        }
+        let height = height as usize;
+        for _ in 0..comment_lines {
+            lines.next();
+        }
+        let read_glyph = |lines: &mut dyn Iterator<Item = (usize, &str)>| {
+            (0..height)
+                .map(|_| lines.next().map(|(_, line)| glyph_row(line)))
+                .collect::<Option<Vec<String>>>()
+        };
+        let mut glyphs = HashMap::new();
+        for code in (32..=126).chain(GERMAN_CHARACTERS) {
+            match read_glyph(&mut lines) {
+                Some(glyph) => {
+                    glyphs.insert(char::from_u32(code).unwrap(), glyph);
+                }
+                // Older fonts stop after ASCII.
+                None if code > 126 => break,
+                None => {
+                    return Err(format!(
+                        "the font ends before the character `{}`",
+                        char::from_u32(code).unwrap()
+                    ))
+                }
+            }
+        }
+        // Further characters are tagged with their code.
+        while let Some((line_number, tag)) = lines.next() {
+            if tag.trim().is_empty() {
+                continue;
+            }
+            let code = tag.split_whitespace().next().unwrap_or_default();
+            let code = parse_code(code).ok_or_else(|| {
+                format!(
+                    "line {}: invalid character code `{}`",
+                    line_number + 1,
+                    code
+                )
+            })?;
+            let glyph = read_glyph(&mut lines)
+                .ok_or_else(|| format!("line {}: the character is incomplete", line_number + 1))?;
+            if let Some(character) = u32::try_from(code).ok().and_then(char::from_u32) {
+                glyphs.insert(character, glyph);
+            }
+        }
+        Ok(Font {
+            height,
+            hardblank,
+            kerning: old_layout >= 0,
+            glyphs,
+        })
+    }
+
+    /// Renders `text` as rows of large letters, or `None` if the font lacks
+    /// one of its characters.
+    pub fn render(&self, text: &str) -> Option<Vec<String>> {
+        let mut rows = vec![String::new(); self.height];
+        for character in text.chars() {
+            let glyph = self.glyphs.get(&character)?;
+            let overlap = match self.kerning {
+                true => rows
+                    .iter()
+                    .zip(glyph)
+                    .map(|(row, glyph_row)| trailing_spaces(row) + leading_spaces(glyph_row))
+                    .min()
+                    .unwrap_or(0),
+                false => 0,
+            };
+            for (row, glyph_row) in rows.iter_mut().zip(glyph) {
+                let from_row = overlap.min(trailing_spaces(row));
+                row.truncate(row.len() - from_row);
+                row.extend(glyph_row.chars().skip(overlap - from_row));
+            }
+        }
+        let rows: Vec<String> = rows
+            .into_iter()
+            .map(|row| row.replace(self.hardblank, " "))
+            .collect();
+        // Drop the spacing after the last character.
+        let blank_columns = rows
+            .iter()
+            .map(|row| trailing_spaces(row))
+            .min()
+            .unwrap_or(0);
+        Some(
+            rows.into_iter()
+                .map(|row| String::from(&row[..row.len() - blank_columns]))
+                .collect(),
+        )
+    }
+
+    /// Renders `text` if it fits into `width` columns.
+    pub fn render_fitting(&self, text: &str, width: usize) -> Option<Vec<String>> {
+        self.render(text)
+            .filter(|rows| rows.iter().all(|row| display_width(row) <= width))
+    }
+}
+
+/// A row of a glyph without its end marks.
+fn glyph_row(line: &str) -> String {
+    let line = line.trim_end();
+    match line.chars().last() {
+        Some(end_mark) => String::from(line.trim_end_matches(end_mark)),
+        None => String::new(),
+    }
+}
+
+/// Parses a character code written in decimal, hex (`0x`) or octal (`0`).
+fn parse_code(code: &str) -> Option<i64> {
+    let (negative, code) = match code.strip_prefix('-') {
+        Some(code) => (true, code),
+        None => (false, code),
+    };
+    let value = if let Some(hex) = code.strip_prefix("0x").or(code.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if code.len() > 1 && code.starts_with('0') {
+        i64::from_str_radix(&code[1..], 8).ok()?
+    } else {
+        code.parse().ok()?
+    };
+    Some(if negative { -value } else { value })
+}
+
+fn trailing_spaces(text: &str) -> usize {
+    text.len() - text.trim_end_matches(' ').len()
+}
+
+fn leading_spaces(text: &str) -> usize {
+    text.len() - text.trim_start_matches(' ').len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FONT: &str = "flf2a$ 2 2 4 0 1\ncomment\n";
+
+    /// A font of width-two glyphs where only `a` and `b` are visible.
+    fn font(old_layout: i64) -> Font {
+        let mut content = FONT.replacen(" 0 1", &format!(" {} 1", old_layout), 1);
+        for code in (32..=126).chain(GERMAN_CHARACTERS) {
+            let (top, bottom) = match char::from_u32(code).unwrap() {
+                'a' => ("a ", "aa"),
+                'b' => (" b", "bb"),
+                _ => ("$$", "$$"),
+            };
+            content.push_str(&format!("{}@\n{}@@\n", top, bottom));
+        }
+        content.push_str("0x263A smiley\n:)@\n:)@@\n");
+        Font::parse(&content).unwrap()
+    }
+
+    #[test]
+    fn test_render_with_kerning() {
+        assert_eq!(font(0).render("ab").unwrap(), vec!["a  b", "aabb"]);
+        assert_eq!(font(-1).render("ab").unwrap(), vec!["a  b", "aabb"]);
+        assert_eq!(font(0).render("ba").unwrap(), vec![" ba ", "bbaa"]);
+        assert_eq!(font(-1).render("a b").unwrap(), vec!["a    b", "aa  bb"]);
+    }
+
+    #[test]
+    fn test_code_tagged_characters() {
+        assert_eq!(font(0).render("☺").unwrap(), vec![":)", ":)"]);
+        assert_eq!(font(0).render("é"), None);
+    }
+
+    #[test]
+    fn test_bundled_fonts() {
+        for (name, _) in BUNDLED {
+            let font = Font::bundled(name).unwrap();
+            assert!(font.render("Hello, World!").is_some());
+        }
+        let rows = Font::bundled("compact").unwrap().render("Hi").unwrap();
+        assert_eq!(rows, vec!["█   █ ▀█▀", "█▀▀▀█  █ ", "▀   ▀ ▀▀▀"]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Font::parse("hello")
+            .unwrap_err()
+            .contains("not a FIGlet font"));
+        assert!(Font::parse("flf2a$ 2 2 4 0 0\n$@\n$@@\n")
+            .unwrap_err()
+            .contains("before the character `!`"));
+    }
+}
@@ -0,0 +1,585 @@
+use std::fmt::{self, Display};
+
+/// A value of the YAML subset front matter is written in: scalars, lists and
+/// nested mappings. Scalars are kept as strings and converted by their user.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    List(Vec<Value>),
+    Map(Vec<Entry>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub key: String,
+    pub value: Value,
+    /// The line of the key, counted from the start of the file.
+    pub line: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Error {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Error {
+    pub fn new(line: usize, message: impl Into<String>) -> Error {
+        Error {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Splits the front matter, a block between two `---` lines at the very start
+/// of `content`, from the rest. Returns no entries without front matter.
+/// `content` starts at line `first_line` of the file.
+pub fn split(content: &str, first_line: usize) -> Result<(Vec<Entry>, &str), Error> {
+    if content.split_inclusive('\n').next().map(str::trim_end) != Some("---") {
+        return Ok((Vec::new(), content));
+    }
+    let (yaml, rest) = block(content)
+        .ok_or_else(|| Error::new(first_line, "the block is not closed with `---`"))?;
+    Ok((parse(yaml, first_line + 1)?, rest))
+}
+
+/// Splits a slide's options block like [`split`], but only when the block is
+/// made of `key: value` lines. Anything else, like a `---` rule followed by
+/// text, is left to the markdown.
+pub fn split_options(content: &str, first_line: usize) -> Result<(Vec<Entry>, &str), Error> {
+    match block(content) {
+        Some((yaml, rest)) if is_mapping(yaml) => Ok((parse(yaml, first_line + 1)?, rest)),
+        _ => Ok((Vec::new(), content)),
+    }
+}
+
+/// The inside of a closed block between two `---` lines at the start of
+/// `content`, and the rest after it.
+fn block(content: &str) -> Option<(&str, &str)> {
+    let mut lines = content.split_inclusive('\n');
+    if lines.next().map(str::trim_end) != Some("---") {
+        return None;
+    }
+    let start = content.find('\n').map_or(content.len(), |end| end + 1);
+    let mut offset = start;
+    for line in lines {
+        if matches!(line.trim_end(), "---" | "...") {
+            return Some((&content[start..offset], &content[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+/// Whether every unindented line of `yaml` starts with a key, so that a
+/// block holding text isn't mistaken for a mapping.
+fn is_mapping(yaml: &str) -> bool {
+    let mut lines = yaml
+        .lines()
+        .filter(|text| !text.trim().is_empty() && !text.trim_start().starts_with('#'))
+        .peekable();
+    lines.peek().is_some_and(|text| indentation(text) == 0)
+        && lines
+            .filter(|text| indentation(text) == 0)
+            .all(|text| split_key(text.trim_end()).is_some())
+}
+
+/// Parses a YAML mapping whose first line is line `first_line` of the file.
+pub fn parse(yaml: &str, first_line: usize) -> Result<Vec<Entry>, Error> {
+    let mut parser = Parser {
+        lines: yaml
+            .lines()
+            .enumerate()
+            .map(|(i, text)| (first_line + i, text))
+            .collect(),
+        position: 0,
+    };
+    if let Some((line, _)) = parser
+        .lines
+        .iter()
+        .find(|(_, text)| text.trim_start_matches(' ').starts_with('\t'))
+    {
+        return Err(Error::new(*line, "tabs are not allowed for indentation"));
+    }
+    let entries = parser.mapping(0)?;
+    match parser.peek() {
+        Some((line, _)) => Err(Error::new(line, "unexpected indentation")),
+        None => Ok(entries),
+    }
+}
+
+struct Parser<'a> {
+    lines: Vec<(usize, &'a str)>,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    /// The next line with content and its indentation, skipping blank lines
+    /// and comments.
+    fn peek(&mut self) -> Option<(usize, usize)> {
+        while let Some((line, text)) = self.lines.get(self.position) {
+            let content = text.trim_start();
+            if !content.is_empty() && !content.starts_with('#') {
+                return Some((*line, indentation(text)));
+            }
+            self.position += 1;
+        }
+        None
+    }
+
+    fn mapping(&mut self, indent: usize) -> Result<Vec<Entry>, Error> {
+        let mut entries: Vec<Entry> = Vec::new();
+        while let Some((line, line_indent)) = self.peek() {
+            if line_indent < indent {
+                break;
+            }
+            if line_indent > indent {
+                return Err(Error::new(line, "unexpected indentation"));
+            }
+            let text = self.lines[self.position].1.trim();
+            if text == "-" || text.starts_with("- ") {
+                return Err(Error::new(line, "expected `key: value`, found a list item"));
+            }
+            let (key, rest) = split_key(text).ok_or_else(|| {
+                Error::new(line, format!("expected `key: value`, found `{}`", text))
+            })?;
+            if entries.iter().any(|entry| entry.key == key) {
+                return Err(Error::new(line, format!("duplicate key `{}`", key)));
+            }
+            self.position += 1;
+            let value = self.value(rest, line, indent)?;
+            entries.push(Entry { key, value, line });
+        }
+        Ok(entries)
+    }
+
+    /// Parses the value following a key or list item, `rest` being the part
+    /// on the same line.
+    fn value(&mut self, rest: &str, line: usize, indent: usize) -> Result<Value, Error> {
+        if rest.starts_with(['"', '\'']) {
+            return self.quoted(rest, line, indent).map(Value::String);
+        }
+        if let Some(items) = rest.strip_prefix('[') {
+            return flow_list(items, line);
+        }
+        let rest = strip_comment(rest);
+        if rest.is_empty() {
+            return match self.peek() {
+                Some((_, next_indent)) if self.is_list_item() && next_indent >= indent => {
+                    self.list(next_indent)
+                }
+                Some((_, next_indent)) if next_indent > indent => {
+                    Ok(Value::Map(self.mapping(next_indent)?))
+                }
+                _ => Ok(Value::String(String::new())),
+            };
+        }
+        if let Some(header) = rest.strip_prefix(['|', '>']) {
+            return self.block_scalar(rest.starts_with('|'), header, line, indent);
+        }
+        if rest.starts_with('{') {
+            return Err(Error::new(line, "flow mappings are not supported"));
+        }
+        if rest.starts_with(['&', '*', '!']) {
+            return Err(Error::new(
+                line,
+                "anchors, aliases and tags are not supported",
+            ));
+        }
+        // Plain values continue on more indented lines, joined by spaces.
+        let mut value = String::from(rest);
+        while let Some((_, next_indent)) = self.peek() {
+            let text = self.lines[self.position].1.trim();
+            if next_indent <= indent || split_key(text).is_some() {
+                break;
+            }
+            value.push(' ');
+            value.push_str(strip_comment(text));
+            self.position += 1;
+        }
+        Ok(Value::String(value))
+    }
+
+    fn is_list_item(&self) -> bool {
+        let text = self.lines[self.position].1.trim_start();
+        text == "-" || text.starts_with("- ")
+    }
+
+    fn list(&mut self, indent: usize) -> Result<Value, Error> {
+        let mut items = Vec::new();
+        while let Some((line, line_indent)) = self.peek() {
+            if line_indent != indent || !self.is_list_item() {
+                if line_indent > indent {
+                    return Err(Error::new(line, "unexpected indentation"));
+                }
+                break;
+            }
+            let text = self.lines[self.position].1.trim();
+            let rest = text[1..].trim_start();
+            if split_key(rest).is_some() {
+                return Err(Error::new(line, "lists of mappings are not supported"));
+            }
+            self.position += 1;
+            items.push(self.value(rest, line, indent + 1)?);
+        }
+        Ok(Value::List(items))
+    }
+
+    /// Parses a `|` (literal) or `>` (folded) block scalar, optionally followed
+    /// by `-` to strip or `+` to keep trailing line breaks.
+    fn block_scalar(
+        &mut self,
+        literal: bool,
+        header: &str,
+        line: usize,
+        indent: usize,
+    ) -> Result<Value, Error> {
+        let chomping = match strip_comment(header) {
+            "" => None,
+            "-" => Some(false),
+            "+" => Some(true),
+            header => {
+                return Err(Error::new(
+                    line,
+                    format!("invalid block scalar header `{}`", header),
+                ))
+            }
+        };
+        let mut lines: Vec<&str> = Vec::new();
+        let mut block_indent = None;
+        while let Some((_, text)) = self.lines.get(self.position) {
+            if text.trim().is_empty() {
+                lines.push("");
+                self.position += 1;
+                continue;
+            }
+            let text_indent = indentation(text);
+            if text_indent <= indent || text_indent < block_indent.unwrap_or(0) {
+                break;
+            }
+            let block_indent = *block_indent.get_or_insert(text_indent);
+            lines.push(&text[block_indent..]);
+            self.position += 1;
+        }
+        let trailing = lines
+            .iter()
+            .rev()
+            .take_while(|text| text.is_empty())
+            .count();
+        // Trailing blank lines belong to what follows unless they are kept.
+        if chomping != Some(true) {
+            self.position -= trailing;
+        }
+        let content = &lines[..lines.len() - trailing];
+        let mut value = String::new();
+        for (i, text) in content.iter().enumerate() {
+            if i > 0 {
+                // Folding turns a line break between two lines into a space
+                // and drops the one before blank lines, except around more
+                // indented lines.
+                let previous = content[i - 1];
+                let folds = !literal && !previous.is_empty() && !previous.starts_with(' ');
+                match (folds, text.is_empty() || text.starts_with(' ')) {
+                    (true, false) => value.push(' '),
+                    (true, true) if text.is_empty() => {}
+                    _ => value.push('\n'),
+                }
+            }
+            value.push_str(text);
+        }
+        if !content.is_empty() {
+            match chomping {
+                None => value.push('\n'),
+                Some(false) => {}
+                Some(true) => value.push_str(&"\n".repeat(trailing + 1)),
+            }
+        }
+        Ok(Value::String(value))
+    }
+
+    /// Parses a single or double quoted string, which may span several lines.
+    /// Line breaks are folded into spaces, blank lines into line breaks.
+    fn quoted(&mut self, rest: &str, line: usize, indent: usize) -> Result<String, Error> {
+        let quote = rest.chars().next().unwrap();
+        let mut value = String::new();
+        let mut text = &rest[1..];
+        let mut current_line = line;
+        let mut after_blank_line = false;
+        loop {
+            let mut escaped_line_break = false;
+            let mut chars = text.char_indices().peekable();
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '\'' if quote == '\'' && chars.peek().map(|(_, c)| *c) == Some('\'') => {
+                        chars.next();
+                        value.push('\'');
+                    }
+                    c if c == quote => {
+                        return after_quote(&text[i + 1..], current_line).map(|_| value);
+                    }
+                    '\\' if quote == '"' => {
+                        let escaped = match chars.next() {
+                            Some((_, 'n')) => '\n',
+                            Some((_, 't')) => '\t',
+                            Some((_, '0')) => '\0',
+                            Some((_, c @ ('"' | '\\' | '/' | ' '))) => c,
+                            Some((_, 'u')) => {
+                                let digits: String =
+                                    chars.by_ref().take(4).map(|(_, c)| c).collect();
+                                u32::from_str_radix(&digits, 16)
+                                    .ok()
+                                    .and_then(char::from_u32)
+                                    .ok_or_else(|| {
+                                        Error::new(
+                                            current_line,
+                                            format!("invalid escape `\\u{}`", digits),
+                                        )
+                                    })?
+                            }
+                            Some((_, c)) => {
+                                return Err(Error::new(
+                                    current_line,
+                                    format!("invalid escape `\\{}`", c),
+                                ))
+                            }
+                            None => {
+                                escaped_line_break = true;
+                                continue;
+                            }
+                        };
+                        value.push(escaped);
+                    }
+                    c => value.push(c),
+                }
+            }
+            match self.lines.get(self.position) {
+                Some((next_line, next)) if next.trim().is_empty() || indentation(next) > indent => {
+                    if !escaped_line_break {
+                        value.truncate(value.trim_end_matches(' ').len());
+                        if next.trim().is_empty() {
+                            value.push('\n');
+                        } else if !after_blank_line {
+                            value.push(' ');
+                        }
+                    }
+                    after_blank_line = next.trim().is_empty();
+                    current_line = *next_line;
+                    text = next.trim_start();
+                    self.position += 1;
+                }
+                _ => return Err(Error::new(line, format!("unclosed {} quote", quote))),
+            }
+        }
+    }
+}
+
+fn after_quote(rest: &str, line: usize) -> Result<(), Error> {
+    match strip_comment(rest) {
+        "" => Ok(()),
+        rest => Err(Error::new(
+            line,
+            format!("unexpected `{}` after the value", rest),
+        )),
+    }
+}
+
+fn flow_list(items: &str, line: usize) -> Result<Value, Error> {
+    let Some(end) = items.rfind(']') else {
+        return Err(Error::new(line, "unclosed `[`"));
+    };
+    after_quote(&items[end + 1..], line)?;
+    let items = &items[..end];
+    if items.contains(['[', '{']) {
+        return Err(Error::new(
+            line,
+            "nested flow collections are not supported",
+        ));
+    }
+    let mut values = Vec::new();
+    let mut rest = items.trim();
+    while !rest.is_empty() {
+        let (item, remainder) = if rest.starts_with(['"', '\'']) {
+            let quote = rest.chars().next().unwrap();
+            let end = rest[1..]
+                .find(quote)
+                .ok_or_else(|| Error::new(line, format!("unclosed {} quote", quote)))?;
+            let remainder = rest[end + 2..].trim_start();
+            if !remainder.is_empty() && !remainder.starts_with(',') {
+                return Err(Error::new(line, "expected `,` between list items"));
+            }
+            (&rest[1..end + 1], remainder)
+        } else {
+            let end = rest.find(',').unwrap_or(rest.len());
+            (rest[..end].trim(), &rest[end..])
+        };
+        values.push(Value::String(String::from(item)));
+        rest = remainder
+            .strip_prefix(',')
+            .unwrap_or(remainder)
+            .trim_start();
+    }
+    Ok(Value::List(values))
+}
+
+/// Splits `key: value` at the colon, which must be followed by a space or the
+/// end of the line.
+fn split_key(text: &str) -> Option<(String, &str)> {
+    if let Some(quoted) = text.strip_prefix(['"', '\'']) {
+        let quote = text.chars().next().unwrap();
+        let end = quoted.find(quote)?;
+        let rest = quoted[end + 1..].strip_prefix(':')?;
+        return (rest.is_empty() || rest.starts_with(' '))
+            .then(|| (String::from(&quoted[..end]), rest.trim_start()));
+    }
+    let colon = text
+        .match_indices(':')
+        .map(|(i, _)| i)
+        .find(|&i| matches!(text.as_bytes().get(i + 1), None | Some(b' ')))?;
+    let key = text[..colon].trim_end();
+    if key.is_empty() || key.starts_with(['#', '[', '{']) {
+        return None;
+    }
+    Some((String::from(key), text[colon + 1..].trim_start()))
+}
+
+/// Removes a ` # comment` from the end of an unquoted value.
+fn strip_comment(text: &str) -> &str {
+    let end = text
+        .match_indices('#')
+        .map(|(i, _)| i)
+        .find(|&i| i == 0 || text[..i].ends_with([' ', '\t']))
+        .unwrap_or(text.len());
+    text[..end].trim()
+}
+
+fn indentation(text: &str) -> usize {
+    text.len() - text.trim_start_matches(' ').len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(value: &str) -> Value {
+        Value::String(String::from(value))
+    }
+
+    fn values(yaml: &str) -> Vec<(String, Value)> {
+        parse(yaml, 1)
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.key, entry.value))
+            .collect()
+    }
+
+    #[test]
+    fn test_split_only_the_leading_block() {
+        let (entries, rest) = split("---\ntitle: a\n---\n# Slide\n---\ntitle: b\n", 1).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].value, string("a"));
+        assert_eq!(entries[0].line, 2);
+        assert_eq!(rest, "# Slide\n---\ntitle: b\n");
+
+        let (entries, rest) = split("# Slide\ntitle: a\n", 1).unwrap();
+        assert!(entries.is_empty());
+        assert_eq!(rest, "# Slide\ntitle: a\n");
+    }
+
+    #[test]
+    fn test_split_options_only_from_key_value_blocks() {
+        let (entries, rest) = split_options("---\nlayout: blank\n---\n# Slide\n", 1).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(rest, "# Slide\n");
+
+        for content in ["---\n\nSome text\n", "---\ntext\n---\n", "---\n---\n"] {
+            let (entries, rest) = split_options(content, 1).unwrap();
+            assert!(entries.is_empty());
+            assert_eq!(rest, content);
+        }
+
+        assert_eq!(
+            split_options("---\nlayout: a\nlayout: b\n---\n", 1)
+                .unwrap_err()
+                .to_string(),
+            "line 3: duplicate key `layout`"
+        );
+    }
+
+    #[test]
+    fn test_parse_scalars() {
+        assert_eq!(
+            values(
+                "a: plain # comment\nb: \"say \\\"hi\\\"\"\nc: 'it''s'\nd: long\n  value\n\"e: f\": g"
+            ),
+            vec![
+                (String::from("a"), string("plain")),
+                (String::from("b"), string("say \"hi\"")),
+                (String::from("c"), string("it's")),
+                (String::from("d"), string("long value")),
+                (String::from("e: f"), string("g")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_block_scalars() {
+        assert_eq!(
+            values("a: |\n  one\n  two\nb: >-\n  one\n  two\n\n  three\nc: x"),
+            vec![
+                (String::from("a"), string("one\ntwo\n")),
+                (String::from("b"), string("one two\nthree")),
+                (String::from("c"), string("x")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_lists_and_maps() {
+        assert_eq!(
+            values("a:\n  - x\n  - 'y'\nb: [1, \"2, 3\"]\nc:\n  d: e"),
+            vec![
+                (
+                    String::from("a"),
+                    Value::List(vec![string("x"), string("y")])
+                ),
+                (
+                    String::from("b"),
+                    Value::List(vec![string("1"), string("2, 3")])
+                ),
+                (
+                    String::from("c"),
+                    Value::Map(vec![Entry {
+                        key: String::from("d"),
+                        value: string("e"),
+                        line: 6,
+                    }])
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_errors_have_line_numbers() {
+        let error = |content: &str| split(content, 1).unwrap_err().to_string();
+        assert_eq!(
+            error("---\ntitle: a\nnot a key\n---\n"),
+            "line 3: expected `key: value`, found `not a key`"
+        );
+        assert_eq!(
+            error("---\ntitle: a\ntitle: b\n---\n"),
+            "line 3: duplicate key `title`"
+        );
+        assert_eq!(error("---\ntitle: \"a\n---\n"), "line 2: unclosed \" quote");
+        assert_eq!(
+            error("---\ntitle: a\n"),
+            "line 1: the block is not closed with `---`"
+        );
+    }
+}
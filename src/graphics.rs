@@ -0,0 +1,328 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    sync::Arc,
+};
+
+use termion::{color::Rgb, terminal_size, terminal_size_pixels};
+
+use crate::{
+    image::Image,
+    terminal::GraphicsProtocol,
+    text::{Line, Span, Style},
+};
+
+/// The size of a terminal cell in pixels, for terminals that don't tell.
+const DEFAULT_CELL_SIZE: (usize, usize) = (10, 20);
+/// The characters of ASCII images, from dark to bright.
+const ASCII_RAMP: &[u8] = b" .:-=+*#%@";
+/// The Kitty protocol takes the image data in pieces of at most 4096 bytes.
+const KITTY_CHUNK_SIZE: usize = 4096;
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// The width and height of a terminal cell in pixels.
+pub fn cell_size() -> (usize, usize) {
+    match (terminal_size(), terminal_size_pixels()) {
+        (Ok((columns, rows)), Ok((width, height)))
+            if columns > 0 && rows > 0 && width >= columns && height >= rows =>
+        {
+            ((width / columns) as usize, (height / rows) as usize)
+        }
+        _ => DEFAULT_CELL_SIZE,
+    }
+}
+
+/// The columns and rows to show `image` in: its own size, shrunk to at most
+/// `width` columns and `height` rows keeping its aspect ratio.
+pub fn fit(image: &Image, cell: (usize, usize), width: usize, height: usize) -> (usize, usize) {
+    let (cell_width, cell_height) = cell;
+    let (width, height) = (width.max(1), height.max(1));
+    let columns = image.width.div_ceil(cell_width).clamp(1, width);
+    let rows = (columns * cell_width * image.height * 2 / (image.width * cell_height)).div_ceil(2);
+    if rows <= height {
+        return (columns, rows.max(1));
+    }
+    let columns = height * cell_height * image.width / (image.height * cell_width);
+    (columns.clamp(1, width), height)
+}
+
+/// The escape sequence removing the images from the screen, for protocols
+/// whose images aren't removed with the text.
+pub fn clear_images(protocol: GraphicsProtocol) -> &'static str {
+    match protocol {
+        GraphicsProtocol::Kitty => "\x1b_Ga=d,q=2\x1b\\",
+        _ => "",
+    }
+}
+
+/// Renders `image` into lines of `columns` × `rows` cells.
+pub fn render(image: &Image, protocol: GraphicsProtocol, columns: usize, rows: usize) -> Vec<Line> {
+    let (cell_width, cell_height) = cell_size();
+    let scaled = || image.resize(columns * cell_width, rows * cell_height);
+    let sequence = match protocol {
+        GraphicsProtocol::HalfBlocks => return half_blocks(&image.resize(columns, rows * 2)),
+        GraphicsProtocol::Ascii => return ascii(&image.resize(columns, rows)),
+        GraphicsProtocol::Kitty => kitty(&scaled(), columns, rows),
+        GraphicsProtocol::Iterm2 => iterm2(&scaled(), columns, rows),
+        GraphicsProtocol::Sixel => sixel(&scaled()),
+    };
+    let sequence: Arc<str> = Arc::from(sequence);
+    let covered: Arc<str> = Arc::from("");
+    (0..rows)
+        .map(|row| {
+            let sequence = if row == 0 { &sequence } else { &covered };
+            Line::new(vec![Span::graphic(sequence.clone(), columns)])
+        })
+        .collect()
+}
+
+/// Images rendered for the terminal by their URL and size, so that drawing a
+/// slide again doesn't scale and encode its images again.
+#[derive(Default)]
+pub struct Cache(RefCell<HashMap<(String, usize, usize), Vec<Line>>>);
+
+impl Cache {
+    /// Renders `image` like [`render`], or returns the lines it was rendered
+    /// into before.
+    pub fn render(
+        &self,
+        url: &str,
+        image: &Image,
+        protocol: GraphicsProtocol,
+        columns: usize,
+        rows: usize,
+    ) -> Vec<Line> {
+        self.0
+            .borrow_mut()
+            .entry((String::from(url), columns, rows))
+            .or_insert_with(|| render(image, protocol, columns, rows))
+            .clone()
+    }
+
+    /// Forgets the rendered images, for when the images or the size of the
+    /// terminal's cells may have changed.
+    pub fn clear(&mut self) {
+        self.0.get_mut().clear();
+    }
+}
+
+fn kitty(image: &Image, columns: usize, rows: usize) -> String {
+    let data = base64(&image.rgba());
+    let chunks: Vec<&str> = data
+        .as_bytes()
+        .chunks(KITTY_CHUNK_SIZE)
+        .map(|chunk| std::str::from_utf8(chunk).unwrap())
+        .collect();
+    let mut sequence = String::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(i + 1 < chunks.len());
+        // Sent without moving the cursor (C=1) or answering (q=2), which
+        // would end up in the key input.
+        match i {
+            0 => write!(
+                sequence,
+                "\x1b_Ga=T,f=32,s={},v={},c={},r={},C=1,q=2,m={};{}\x1b\\",
+                image.width, image.height, columns, rows, more, chunk
+            ),
+            _ => write!(sequence, "\x1b_Gm={};{}\x1b\\", more, chunk),
+        }
+        .unwrap();
+    }
+    sequence
+}
+
+fn iterm2(image: &Image, columns: usize, rows: usize) -> String {
+    let png = image.to_png();
+    format!(
+        "\x1b]1337;File=inline=1;size={};width={};height={};preserveAspectRatio=0:{}\x07",
+        png.len(),
+        columns,
+        rows,
+        base64(&png)
+    )
+}
+
+/// Draws the image with the colours of a 6 × 6 × 6 colour cube, leaving out
+/// transparent pixels.
+fn sixel(image: &Image) -> String {
+    let mut sequence = format!("\x1bP0;1;0q\"1;1;{};{}", image.width, image.height);
+    for index in 0..216 {
+        let (red, green, blue) = (index / 36, index / 6 % 6, index % 6);
+        write!(
+            sequence,
+            "#{};2;{};{};{}",
+            index,
+            red * 20,
+            green * 20,
+            blue * 20
+        )
+        .unwrap();
+    }
+    let level = |value: u8| (value as usize * 5 + 127) / 255;
+    for top in (0..image.height).step_by(6) {
+        // Each colour's pixels in the band of six rows, a bit per row.
+        let mut colors: BTreeMap<usize, Vec<u8>> = BTreeMap::new();
+        for row in 0..6.min(image.height - top) {
+            for x in 0..image.width {
+                let [red, green, blue, alpha] = image.pixel(x, top + row);
+                if alpha < 128 {
+                    continue;
+                }
+                let color = level(red) * 36 + level(green) * 6 + level(blue);
+                colors.entry(color).or_insert_with(|| vec![0; image.width])[x] |= 1 << row;
+            }
+        }
+        for (color, columns) in colors {
+            write!(sequence, "#{}", color).unwrap();
+            let mut x = 0;
+            while x < columns.len() {
+                let run = columns[x..]
+                    .iter()
+                    .take_while(|bits| **bits == columns[x])
+                    .count();
+                let character = (63 + columns[x]) as char;
+                match run {
+                    1..=3 => sequence.extend((0..run).map(|_| character)),
+                    _ => write!(sequence, "!{}{}", run, character).unwrap(),
+                }
+                x += run;
+            }
+            sequence.push('$');
+        }
+        sequence.push('-');
+    }
+    sequence.push_str("\x1b\\");
+    sequence
+}
+
+/// Two pixels per cell: the upper one as the colour of `▀`, the lower one as
+/// its background.
+fn half_blocks(image: &Image) -> Vec<Line> {
+    let color =
+        |[red, green, blue, alpha]: [u8; 4]| (alpha >= 128).then_some(Rgb(red, green, blue));
+    (0..image.height / 2)
+        .map(|row| {
+            let spans = (0..image.width)
+                .map(|x| {
+                    let top = color(image.pixel(x, row * 2));
+                    let bottom = color(image.pixel(x, row * 2 + 1));
+                    match (top, bottom) {
+                        (Some(top), Some(bottom)) => {
+                            Span::new("▀", Style::fg(top).background(bottom))
+                        }
+                        (Some(top), None) => Span::new("▀", Style::fg(top)),
+                        (None, Some(bottom)) => Span::new("▄", Style::fg(bottom)),
+                        (None, None) => Span::plain(" "),
+                    }
+                })
+                .collect();
+            Line::new(spans)
+        })
+        .collect()
+}
+
+/// A character per pixel, denser for brighter pixels.
+fn ascii(image: &Image) -> Vec<Line> {
+    (0..image.height)
+        .map(|y| {
+            let text: String = (0..image.width)
+                .map(|x| {
+                    let [red, green, blue, alpha] = image.pixel(x, y);
+                    let brightness =
+                        (299 * red as usize + 587 * green as usize + 114 * blue as usize)
+                            * alpha as usize
+                            / (1000 * 255);
+                    ASCII_RAMP[brightness * (ASCII_RAMP.len() - 1) / 255] as char
+                })
+                .collect();
+            Line::new(vec![Span::plain(text)])
+        })
+        .collect()
+}
+
+fn base64(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            0,
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = u32::from_be_bytes(bytes);
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => encoded.push(BASE64_ALPHABET[(bits >> (18 - 6 * i) & 63) as usize] as char),
+                false => encoded.push('='),
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(width: usize, height: usize) -> Image {
+        Image::from_fn(width, height, |x, y| match (x + y) % 2 {
+            0 => [255, 255, 255, 255],
+            _ => [0, 0, 0, 0],
+        })
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b"Man"), "TWFu");
+        assert_eq!(base64(b"Ma"), "TWE=");
+        assert_eq!(base64(b"M"), "TQ==");
+    }
+
+    #[test]
+    fn test_fit_keeps_aspect_ratio() {
+        let image = image(400, 200);
+        // 40 × 10 cells of 10 × 20 pixels at its own size.
+        assert_eq!(fit(&image, (10, 20), 80, 20), (40, 10));
+        assert_eq!(fit(&image, (10, 20), 20, 20), (20, 5));
+        assert_eq!(fit(&image, (10, 20), 80, 2), (8, 2));
+    }
+
+    #[test]
+    fn test_half_blocks() {
+        let lines = half_blocks(&image(2, 2));
+        let white = Rgb(255, 255, 255);
+        assert_eq!(
+            lines,
+            vec![Line::new(vec![
+                Span::new("▀", Style::fg(white)),
+                Span::new("▄", Style::fg(white)),
+            ])]
+        );
+    }
+
+    #[test]
+    fn test_sixel() {
+        let sequence = sixel(&image(5, 2));
+        // White in rows 0 and 1 alternately, run-length encoded when repeated.
+        assert!(sequence.ends_with("#215@A@A@$-\x1b\\"));
+        let sequence = sixel(&Image::from_fn(8, 1, |_, _| [0, 0, 0, 255]));
+        assert!(sequence.ends_with("#0!8@$-\x1b\\"));
+    }
+
+    #[test]
+    fn test_cache_encodes_each_size_once() {
+        let mut cache = Cache::default();
+        let image = image(4, 4);
+        let sequence = |lines: Vec<Line>| lines[0].spans[0].graphic.clone().unwrap();
+        let first = sequence(cache.render("a.png", &image, GraphicsProtocol::Kitty, 2, 1));
+        let again = sequence(cache.render("a.png", &image, GraphicsProtocol::Kitty, 2, 1));
+        assert!(Arc::ptr_eq(&first, &again));
+        let smaller = sequence(cache.render("a.png", &image, GraphicsProtocol::Kitty, 1, 1));
+        assert!(!Arc::ptr_eq(&first, &smaller));
+        cache.clear();
+        let cleared = sequence(cache.render("a.png", &image, GraphicsProtocol::Kitty, 2, 1));
+        assert!(!Arc::ptr_eq(&first, &cleared));
+    }
+}
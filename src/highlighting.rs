@@ -0,0 +1,369 @@
+use crate::{
+    colors::SyntaxColors,
+    text::{Line, Span, Style},
+};
+
+struct Language {
+    keywords: &'static [&'static str],
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    /// Whether `'` starts a string literal rather than a char or lifetime.
+    single_quote_strings: bool,
+}
+
+const RUST: Language = Language {
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+        "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+        "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait",
+        "true", "type", "unsafe", "use", "where", "while",
+    ],
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    single_quote_strings: false,
+};
+
+const C_LIKE: Language = Language {
+    keywords: &[
+        "auto",
+        "break",
+        "case",
+        "catch",
+        "class",
+        "const",
+        "continue",
+        "default",
+        "delete",
+        "do",
+        "else",
+        "enum",
+        "extends",
+        "false",
+        "final",
+        "for",
+        "func",
+        "go",
+        "if",
+        "implements",
+        "import",
+        "interface",
+        "namespace",
+        "new",
+        "nil",
+        "null",
+        "nullptr",
+        "package",
+        "private",
+        "protected",
+        "public",
+        "return",
+        "sizeof",
+        "static",
+        "struct",
+        "switch",
+        "this",
+        "throw",
+        "throws",
+        "true",
+        "try",
+        "typedef",
+        "union",
+        "var",
+        "void",
+        "while",
+    ],
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    single_quote_strings: false,
+};
+
+const JAVASCRIPT: Language = Language {
+    keywords: &[
+        "async",
+        "await",
+        "break",
+        "case",
+        "catch",
+        "class",
+        "const",
+        "continue",
+        "default",
+        "delete",
+        "do",
+        "else",
+        "export",
+        "extends",
+        "false",
+        "finally",
+        "for",
+        "from",
+        "function",
+        "if",
+        "import",
+        "in",
+        "instanceof",
+        "interface",
+        "let",
+        "new",
+        "null",
+        "of",
+        "return",
+        "static",
+        "switch",
+        "this",
+        "throw",
+        "true",
+        "try",
+        "type",
+        "typeof",
+        "undefined",
+        "var",
+        "void",
+        "while",
+        "yield",
+    ],
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    single_quote_strings: true,
+};
+
+const PYTHON: Language = Language {
+    keywords: &[
+        "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+        "elif", "else", "except", "False", "finally", "for", "from", "global", "if", "import",
+        "in", "is", "lambda", "None", "nonlocal", "not", "or", "pass", "raise", "return", "self",
+        "True", "try", "while", "with", "yield",
+    ],
+    line_comments: &["#"],
+    block_comment: None,
+    single_quote_strings: true,
+};
+
+const SHELL: Language = Language {
+    keywords: &[
+        "case", "do", "done", "echo", "elif", "else", "esac", "exit", "export", "fi", "for",
+        "function", "if", "in", "local", "return", "then", "until", "while",
+    ],
+    line_comments: &["#"],
+    block_comment: None,
+    single_quote_strings: true,
+};
+
+const CONFIG: Language = Language {
+    keywords: &["true", "false", "null", "yes", "no"],
+    line_comments: &["#"],
+    block_comment: None,
+    single_quote_strings: true,
+};
+
+fn language(name: &str) -> Option<&'static Language> {
+    match name.to_lowercase().as_str() {
+        "rust" | "rs" => Some(&RUST),
+        "c" | "h" | "cpp" | "c++" | "java" | "kotlin" | "go" | "golang" | "csharp" | "cs" => {
+            Some(&C_LIKE)
+        }
+        "javascript" | "js" | "typescript" | "ts" | "jsx" | "tsx" => Some(&JAVASCRIPT),
+        "python" | "py" => Some(&PYTHON),
+        "bash" | "sh" | "shell" | "zsh" | "fish" => Some(&SHELL),
+        "toml" | "yaml" | "yml" | "json" | "ini" => Some(&CONFIG),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TokenKind {
+    Text,
+    Keyword,
+    String,
+    Number,
+    Comment,
+    Function,
+    TypeName,
+}
+
+/// Highlights `code` line by line. Code in an unknown language is rendered in
+/// the plain text colour.
+pub fn highlight(code: &str, language_name: Option<&str>, colors: &SyntaxColors) -> Vec<Line> {
+    let language = language_name.and_then(language);
+    let mut in_block_comment = false;
+    code.lines()
+        .map(|line| {
+            let line = line.replace('\t', "    ");
+            let tokens = match language {
+                Some(language) => tokenize(&line, language, &mut in_block_comment),
+                None => vec![(TokenKind::Text, line.clone())],
+            };
+            Line::new(
+                tokens
+                    .into_iter()
+                    .map(|(kind, text)| Span::new(text, token_style(kind, colors)))
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
+fn token_style(kind: TokenKind, colors: &SyntaxColors) -> Style {
+    match kind {
+        TokenKind::Text => Style::fg(colors.text),
+        TokenKind::Keyword => Style::fg(colors.keyword).bold(),
+        TokenKind::String => Style::fg(colors.string),
+        TokenKind::Number => Style::fg(colors.number),
+        TokenKind::Comment => Style {
+            italic: true,
+            ..Style::fg(colors.comment)
+        },
+        TokenKind::Function => Style::fg(colors.function),
+        TokenKind::TypeName => Style::fg(colors.type_name),
+    }
+}
+
+fn tokenize(
+    line: &str,
+    language: &Language,
+    in_block_comment: &mut bool,
+) -> Vec<(TokenKind, String)> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut tokens: Vec<(TokenKind, String)> = Vec::new();
+    let mut push = |kind: TokenKind, text: &[char]| match tokens.last_mut() {
+        Some((last, content)) if *last == kind => content.extend(text),
+        _ => tokens.push((kind, text.iter().collect())),
+    };
+    let starts_with = |i: usize, pattern: &str| {
+        pattern
+            .chars()
+            .enumerate()
+            .all(|(j, c)| chars.get(i + j) == Some(&c))
+    };
+    let mut i = 0;
+    while i < chars.len() {
+        if *in_block_comment {
+            let (_, end) = language.block_comment.unwrap();
+            let stop = (i..chars.len())
+                .find(|&j| starts_with(j, end))
+                .map(|j| {
+                    *in_block_comment = false;
+                    j + end.chars().count()
+                })
+                .unwrap_or(chars.len());
+            push(TokenKind::Comment, &chars[i..stop]);
+            i = stop;
+            continue;
+        }
+        let c = chars[i];
+        if language
+            .line_comments
+            .iter()
+            .any(|comment| starts_with(i, comment))
+        {
+            push(TokenKind::Comment, &chars[i..]);
+            break;
+        }
+        if let Some((start, _)) = language.block_comment {
+            if starts_with(i, start) {
+                *in_block_comment = true;
+                push(TokenKind::Comment, &chars[i..i + start.chars().count()]);
+                i += start.chars().count();
+                continue;
+            }
+        }
+        let is_string_quote = c == '"'
+            || c == '`'
+            || (c == '\'' && (language.single_quote_strings || is_char_literal(&chars[i..])));
+        let end = if is_string_quote {
+            let mut j = i + 1;
+            while j < chars.len() && chars[j] != c {
+                j += if chars[j] == '\\' { 2 } else { 1 };
+            }
+            push(TokenKind::String, &chars[i..(j + 1).min(chars.len())]);
+            j + 1
+        } else if c.is_ascii_digit() {
+            let j = scan(&chars, i, |c| {
+                c.is_ascii_alphanumeric() || c == '.' || c == '_'
+            });
+            push(TokenKind::Number, &chars[i..j]);
+            j
+        } else if c.is_alphabetic() || c == '_' {
+            let j = scan(&chars, i, |c| c.is_alphanumeric() || c == '_');
+            let word: String = chars[i..j].iter().collect();
+            let next = chars[j..].iter().find(|c| !c.is_whitespace());
+            let kind = if language.keywords.contains(&word.as_str()) {
+                TokenKind::Keyword
+            } else if matches!(next, Some('(') | Some('!')) {
+                TokenKind::Function
+            } else if c.is_uppercase() {
+                TokenKind::TypeName
+            } else {
+                TokenKind::Text
+            };
+            push(kind, &chars[i..j]);
+            j
+        } else {
+            push(TokenKind::Text, &chars[i..i + 1]);
+            i + 1
+        };
+        i = end;
+    }
+    tokens
+}
+
+fn scan(chars: &[char], start: usize, predicate: impl Fn(char) -> bool) -> usize {
+    (start..chars.len())
+        .find(|&i| !predicate(chars[i]))
+        .unwrap_or(chars.len())
+}
+
+/// Distinguishes `'a'` and `'\n'` from lifetimes like `'a`.
+fn is_char_literal(chars: &[char]) -> bool {
+    matches!(chars, ['\'', '\\', ..] | ['\'', _, '\'', ..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(line: &str, language: &Language) -> Vec<(TokenKind, String)> {
+        tokenize(line, language, &mut false)
+    }
+
+    #[test]
+    fn test_tokenize_rust() {
+        assert_eq!(
+            kinds("let x = foo(\"a\"); // done", &RUST),
+            vec![
+                (TokenKind::Keyword, String::from("let")),
+                (TokenKind::Text, String::from(" x = ")),
+                (TokenKind::Function, String::from("foo")),
+                (TokenKind::Text, String::from("(")),
+                (TokenKind::String, String::from("\"a\"")),
+                (TokenKind::Text, String::from("); ")),
+                (TokenKind::Comment, String::from("// done")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_lifetime_is_not_a_string() {
+        assert_eq!(
+            kinds("&'a str", &RUST),
+            vec![(TokenKind::Text, String::from("&'a str"))]
+        );
+    }
+
+    #[test]
+    fn test_block_comment_spans_lines() {
+        let mut in_block_comment = false;
+        tokenize("x /* start", &C_LIKE, &mut in_block_comment);
+        assert!(in_block_comment);
+        let tokens = tokenize("end */ 1", &C_LIKE, &mut in_block_comment);
+        assert!(!in_block_comment);
+        assert_eq!(
+            tokens,
+            vec![
+                (TokenKind::Comment, String::from("end */")),
+                (TokenKind::Text, String::from(" ")),
+                (TokenKind::Number, String::from("1")),
+            ]
+        );
+    }
+}
@@ -0,0 +1,175 @@
+mod gif;
+mod inflate;
+mod jpeg;
+mod png;
+
+use std::{fs, path::Path};
+
+/// The most pixels an image may have, so that a corrupt or huge file can't
+/// take up all memory. That's 8192 × 4096 pixels, 128 MB decoded.
+const MAX_PIXELS: usize = 1 << 25;
+
+/// A decoded image with 8-bit RGBA pixels, stored row by row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pixels: Vec<[u8; 4]>,
+}
+
+impl Image {
+    /// Loads a PNG, JPEG or GIF file. Of animated GIFs only the first frame
+    /// is shown.
+    pub fn load(path: &Path) -> Result<Image, String> {
+        let data = fs::read(path)
+            .map_err(|err| format!("Error reading image {}: {}", path.display(), err))?;
+        Image::decode(&data).map_err(|err| format!("Error in image {}: {}", path.display(), err))
+    }
+
+    fn decode(data: &[u8]) -> Result<Image, String> {
+        let image = if data.starts_with(png::SIGNATURE) {
+            png::decode(data)?
+        } else if data.starts_with(&[0xff, 0xd8]) {
+            jpeg::decode(data)?
+        } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+            gif::decode(data)?
+        } else {
+            return Err(String::from("not a PNG, JPEG or GIF image"));
+        };
+        if image.width == 0 || image.height == 0 {
+            return Err(String::from("the image is empty"));
+        }
+        Ok(image)
+    }
+
+    #[cfg(test)]
+    pub fn from_fn(width: usize, height: usize, pixel: impl Fn(usize, usize) -> [u8; 4]) -> Image {
+        let pixels = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| pixel(x, y))
+            .collect();
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        self.pixels[y * self.width + x]
+    }
+
+    /// Scales the image to `width` × `height` pixels. Each new pixel is the
+    /// average of the pixels it covers.
+    pub fn resize(&self, width: usize, height: usize) -> Image {
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let (top, bottom) = covered(y, height, self.height);
+            for x in 0..width {
+                let (left, right) = covered(x, width, self.width);
+                let mut sum = [0u32; 4];
+                for row in top..bottom {
+                    for pixel in &self.pixels[row * self.width + left..row * self.width + right] {
+                        for (sum, channel) in sum.iter_mut().zip(pixel) {
+                            *sum += *channel as u32;
+                        }
+                    }
+                }
+                let count = ((bottom - top) * (right - left)) as u32;
+                pixels.push(sum.map(|sum| (sum / count) as u8));
+            }
+        }
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// The pixels as bytes, four per pixel.
+    pub fn rgba(&self) -> Vec<u8> {
+        self.pixels.iter().flatten().copied().collect()
+    }
+
+    /// The image as an uncompressed PNG file.
+    pub fn to_png(&self) -> Vec<u8> {
+        png::encode(self)
+    }
+}
+
+/// Checks the size given in an image's header before memory is allocated for
+/// its pixels.
+fn check_size(width: usize, height: usize) -> Result<(), String> {
+    match width.checked_mul(height) {
+        Some(pixels) if pixels <= MAX_PIXELS => Ok(()),
+        _ => Err(format!(
+            "the image is too large ({} × {} pixels)",
+            width, height
+        )),
+    }
+}
+
+/// The range of the `original` pixels covered by pixel `index` of `scaled`
+/// pixels, at least one pixel wide.
+fn covered(index: usize, scaled: usize, original: usize) -> (usize, usize) {
+    let start = index * original / scaled;
+    let end = ((index + 1) * original / scaled).max(start + 1);
+    (start, end.min(original))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(width: usize, height: usize, pixels: &[[u8; 4]]) -> Image {
+        Image {
+            width,
+            height,
+            pixels: pixels.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_resize_averages_pixels() {
+        let black = [0, 0, 0, 255];
+        let white = [255, 255, 255, 255];
+        let image = image(2, 2, &[black, white, white, black]);
+        assert_eq!(image.resize(1, 1).pixels, vec![[127, 127, 127, 255]]);
+        assert_eq!(image.resize(4, 1).pixels[..2], [[127, 127, 127, 255]; 2]);
+    }
+
+    #[test]
+    fn test_decode_rejects_other_formats() {
+        assert_eq!(
+            Image::decode(b"BM...").unwrap_err(),
+            "not a PNG, JPEG or GIF image"
+        );
+    }
+
+    #[test]
+    fn test_decode_malformed_images() {
+        let png = image(2, 1, &[[255, 0, 0, 255], [0, 0, 255, 128]]).to_png();
+        // Cut off or corrupted, the image may fail to decode but never panics.
+        for length in 0..png.len() {
+            let _ = Image::decode(&png[..length]);
+        }
+        for position in 0..png.len() {
+            for value in [0x00, 0x7f, 0xff] {
+                let mut corrupt = png.clone();
+                corrupt[position] = value;
+                let _ = Image::decode(&corrupt);
+            }
+        }
+        assert_eq!(
+            check_size(1 << 16, 1 << 16).unwrap_err(),
+            "the image is too large (65536 × 65536 pixels)"
+        );
+        assert_eq!(check_size(8192, 4096), Ok(()));
+    }
+
+    #[test]
+    fn test_png_round_trip() {
+        let original = image(2, 1, &[[255, 0, 0, 255], [0, 0, 255, 128]]);
+        assert_eq!(Image::decode(&original.to_png()).unwrap(), original);
+    }
+}
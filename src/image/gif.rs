@@ -0,0 +1,266 @@
+use super::{check_size, Image};
+
+/// The largest LZW code, 12 bits long.
+const MAX_CODES: usize = 4096;
+
+/// Decodes the first frame of a GIF.
+pub fn decode(data: &[u8]) -> Result<Image, String> {
+    let mut reader = Reader { data, position: 6 };
+    let width = reader.u16()? as usize;
+    let height = reader.u16()? as usize;
+    check_size(width, height)?;
+    let flags = reader.byte()?;
+    reader.skip(2)?;
+    let global_palette = match flags & 0x80 {
+        0 => Vec::new(),
+        _ => reader.palette(flags)?,
+    };
+    let mut transparent_index = None;
+    loop {
+        match reader.byte()? {
+            0x21 => {
+                let label = reader.byte()?;
+                let data = reader.sub_blocks()?;
+                // The graphic control extension of the next image.
+                if label == 0xf9 && data.len() >= 4 && data[0] & 1 == 1 {
+                    transparent_index = Some(data[3]);
+                }
+            }
+            0x2c => {
+                let left = reader.u16()? as usize;
+                let top = reader.u16()? as usize;
+                let frame_width = reader.u16()? as usize;
+                let frame_height = reader.u16()? as usize;
+                check_size(frame_width, frame_height)?;
+                let flags = reader.byte()?;
+                let palette = match flags & 0x80 {
+                    0 => global_palette,
+                    _ => reader.palette(flags)?,
+                };
+                let minimum_code_size = reader.byte()?;
+                let indices = lzw(
+                    &reader.sub_blocks()?,
+                    minimum_code_size,
+                    frame_width * frame_height,
+                )?;
+                let rows = match flags & 0x40 {
+                    0 => (0..frame_height).collect(),
+                    _ => interlaced_rows(frame_height),
+                };
+                let mut pixels = vec![[0; 4]; width * height];
+                for (row, indices) in rows.into_iter().zip(indices.chunks(frame_width.max(1))) {
+                    for (column, index) in indices.iter().enumerate() {
+                        let (x, y) = (left + column, top + row);
+                        if x >= width || y >= height || Some(*index) == transparent_index {
+                            continue;
+                        }
+                        if let Some(color) = palette.get(*index as usize) {
+                            pixels[y * width + x] = *color;
+                        }
+                    }
+                }
+                return Ok(Image {
+                    width,
+                    height,
+                    pixels,
+                });
+            }
+            0x3b => return Err(String::from("the GIF has no image")),
+            _ => return Err(String::from("invalid block")),
+        }
+    }
+}
+
+/// The order rows of an interlaced image are stored in.
+fn interlaced_rows(height: usize) -> Vec<usize> {
+    [(0, 8), (4, 8), (2, 4), (1, 2)]
+        .into_iter()
+        .flat_map(|(start, step)| (start..height).step_by(step))
+        .collect()
+}
+
+/// Decompresses the palette indices of an image, stopping after `count`.
+fn lzw(data: &[u8], minimum_code_size: u8, count: usize) -> Result<Vec<u8>, String> {
+    if !(1..=11).contains(&minimum_code_size) {
+        return Err(format!("invalid LZW code size {}", minimum_code_size));
+    }
+    let clear = 1 << minimum_code_size;
+    let end = clear + 1;
+    // Each code stands for the string of its prefix code plus one byte.
+    let mut prefixes = vec![0u16; MAX_CODES];
+    let mut suffixes = vec![0u8; MAX_CODES];
+    let mut lengths = vec![0usize; MAX_CODES];
+    for code in 0..clear {
+        suffixes[code] = code as u8;
+        lengths[code] = 1;
+    }
+    let mut next = end + 1;
+    let mut code_size = minimum_code_size + 1;
+    let mut previous: Option<usize> = None;
+    let mut output = Vec::with_capacity(count);
+    let (mut buffer, mut bits) = (0u32, 0u8);
+    let mut bytes = data.iter();
+    while output.len() < count {
+        while bits < code_size {
+            let Some(byte) = bytes.next() else {
+                break;
+            };
+            buffer |= (*byte as u32) << bits;
+            bits += 8;
+        }
+        if bits < code_size {
+            break;
+        }
+        let code = (buffer & ((1 << code_size) - 1)) as usize;
+        buffer >>= code_size;
+        bits -= code_size;
+        if code == clear {
+            next = end + 1;
+            code_size = minimum_code_size + 1;
+            previous = None;
+            continue;
+        }
+        if code == end {
+            break;
+        }
+        let Some(previous_code) = previous else {
+            if code >= clear {
+                return Err(String::from("invalid LZW code"));
+            }
+            output.push(code as u8);
+            previous = Some(code);
+            continue;
+        };
+        // The first byte of the string of `code`.
+        let first = |mut code: usize| {
+            for _ in 1..lengths[code] {
+                code = prefixes[code] as usize;
+            }
+            suffixes[code]
+        };
+        let added = match code {
+            code if code < next => first(code),
+            code if code == next => first(previous_code),
+            _ => return Err(String::from("invalid LZW code")),
+        };
+        if next < MAX_CODES {
+            prefixes[next] = previous_code as u16;
+            suffixes[next] = added;
+            lengths[next] = lengths[previous_code] + 1;
+            next += 1;
+            if next == 1 << code_size && code_size < 12 {
+                code_size += 1;
+            }
+        }
+        let start = output.len();
+        output.resize(start + lengths[code], 0);
+        let mut string = code;
+        for position in (start..output.len()).rev() {
+            output[position] = suffixes[string];
+            string = prefixes[string] as usize;
+        }
+        previous = Some(code);
+    }
+    output.resize(count, 0);
+    Ok(output)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Result<u8, String> {
+        let byte = *self.data.get(self.position).ok_or("the file ends early")?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_le_bytes([self.byte()?, self.byte()?]))
+    }
+
+    fn skip(&mut self, count: usize) -> Result<(), String> {
+        for _ in 0..count {
+            self.byte()?;
+        }
+        Ok(())
+    }
+
+    /// Reads the colour table announced in `flags`.
+    fn palette(&mut self, flags: u8) -> Result<Vec<[u8; 4]>, String> {
+        (0..2 << (flags & 7))
+            .map(|_| Ok([self.byte()?, self.byte()?, self.byte()?, 255]))
+            .collect()
+    }
+
+    /// Reads blocks of data up to the empty block ending them.
+    fn sub_blocks(&mut self) -> Result<Vec<u8>, String> {
+        let mut data = Vec::new();
+        loop {
+            let length = self.byte()? as usize;
+            if length == 0 {
+                return Ok(data);
+            }
+            let block = self
+                .data
+                .get(self.position..self.position + length)
+                .ok_or("the file ends early")?;
+            data.extend_from_slice(block);
+            self.position += length;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_transparent_pixel() {
+        let gif = b"GIF89a\x01\x00\x01\x00\x80\x00\x00\xff\xff\xff\x00\x00\x00!\xf9\x04\x01\x00\x00\x00\x00,\x00\x00\x00\x00\x01\x00\x01\x00\x00\x02\x02D\x01\x00;";
+        let image = decode(gif).unwrap();
+        assert_eq!((image.width, image.height), (1, 1));
+        assert_eq!(image.pixels, vec![[0, 0, 0, 0]]);
+
+        // Cut off or corrupted, the image may fail to decode but never panics.
+        for length in 0..gif.len() {
+            let _ = decode(&gif[..length]);
+        }
+        for position in 0..gif.len() {
+            for value in [0x00, 0x7f, 0xff] {
+                let mut corrupt = *gif;
+                corrupt[position] = value;
+                let _ = decode(&corrupt);
+            }
+        }
+    }
+
+    #[test]
+    fn test_reject_images_too_large_to_decode() {
+        let gif = b"GIF89a\xff\xff\xff\xff\x00\x00\x00,";
+        assert_eq!(
+            decode(gif).unwrap_err(),
+            "the image is too large (65535 × 65535 pixels)"
+        );
+        // A frame far larger than the screen it is shown on.
+        let gif = b"GIF89a\x01\x00\x01\x00\x00\x00\x00,\x00\x00\x00\x00\xff\xff\xff\xff\x00";
+        assert_eq!(
+            decode(gif).unwrap_err(),
+            "the image is too large (65535 × 65535 pixels)"
+        );
+    }
+
+    #[test]
+    fn test_lzw() {
+        // The codes clear, 1, 6, 6 and end, where the first 6 is defined by
+        // its own use and the code size grows to 4 bits before the end.
+        assert_eq!(lzw(&[0x8c, 0x5d], 2, 7).unwrap(), vec![1, 1, 1, 1, 1, 0, 0]);
+    }
+
+    #[test]
+    fn test_interlaced_rows() {
+        assert_eq!(interlaced_rows(5), vec![0, 4, 2, 1, 3]);
+    }
+}
@@ -0,0 +1,287 @@
+//! Decompression of the zlib data in PNG files (RFC 1950 and 1951).
+
+const LENGTH_BASES: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA_BITS: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASES: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA_BITS: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// The order the lengths of the code length code are stored in.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Decompresses a zlib stream of at most `limit` bytes. The checksum is not
+/// verified.
+pub fn zlib(data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    let [method, flags, ..] = *data else {
+        return Err(String::from("the compressed data is empty"));
+    };
+    if method & 0x0f != 8 || !(method as u16 * 256 + flags as u16).is_multiple_of(31) {
+        return Err(String::from("invalid zlib header"));
+    }
+    if flags & 0x20 != 0 {
+        return Err(String::from("zlib dictionaries are not supported"));
+    }
+    inflate(&data[2..], limit)
+}
+
+/// Decompresses raw deflate data, failing once there are more than `limit`
+/// bytes.
+fn inflate(data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    let mut bits = Bits::new(data);
+    let mut output = Vec::new();
+    let too_long = || String::from("the compressed data is longer than expected");
+    loop {
+        let last = bits.read(1)? == 1;
+        match bits.read(2)? {
+            0 => {
+                bits.align();
+                let length = bits.read(16)?;
+                if bits.read(16)? != !length & 0xffff {
+                    return Err(String::from("invalid length of an uncompressed block"));
+                }
+                if output.len() + length as usize > limit {
+                    return Err(too_long());
+                }
+                for _ in 0..length {
+                    output.push(bits.read(8)? as u8);
+                }
+            }
+            1 => {
+                let (literals, distances) = fixed_codes();
+                inflate_block(&mut bits, &literals, &distances, &mut output, limit)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut bits)?;
+                inflate_block(&mut bits, &literals, &distances, &mut output, limit)?;
+            }
+            _ => return Err(String::from("invalid block type")),
+        }
+        if output.len() > limit {
+            return Err(too_long());
+        }
+        if last {
+            return Ok(output);
+        }
+    }
+}
+
+fn inflate_block(
+    bits: &mut Bits,
+    literals: &Huffman,
+    distances: &Huffman,
+    output: &mut Vec<u8>,
+    limit: usize,
+) -> Result<(), String> {
+    loop {
+        if output.len() > limit {
+            return Err(String::from("the compressed data is longer than expected"));
+        }
+        let symbol = literals.decode(bits)? as usize;
+        match symbol {
+            0..=255 => output.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let index = symbol - 257;
+                let base = *LENGTH_BASES.get(index).ok_or("invalid length code")?;
+                let length = base as usize + bits.read(LENGTH_EXTRA_BITS[index])? as usize;
+                let index = distances.decode(bits)? as usize;
+                let base = *DISTANCE_BASES.get(index).ok_or("invalid distance code")?;
+                let distance = base as usize + bits.read(DISTANCE_EXTRA_BITS[index])? as usize;
+                if distance > output.len() {
+                    return Err(String::from("a distance points before the start"));
+                }
+                // Copied byte by byte since the copy may overlap its source.
+                let start = output.len() - distance;
+                for i in 0..length {
+                    output.push(output[start + i]);
+                }
+            }
+        }
+    }
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [8; 288];
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+/// Reads the codes of a block that brings its own.
+fn dynamic_codes(bits: &mut Bits) -> Result<(Huffman, Huffman), String> {
+    let literal_count = bits.read(5)? as usize + 257;
+    let distance_count = bits.read(5)? as usize + 1;
+    let code_length_count = bits.read(4)? as usize + 4;
+    let mut code_length_lengths = [0; 19];
+    for index in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_length_lengths[*index] = bits.read(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_length_lengths);
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let (length, repeat) = match code_lengths.decode(bits)? {
+            length @ 0..=15 => (length as u8, 1),
+            16 => {
+                let previous = *lengths.last().ok_or("a repeat without a previous length")?;
+                (previous, 3 + bits.read(2)?)
+            }
+            17 => (0, 3 + bits.read(3)?),
+            _ => (0, 11 + bits.read(7)?),
+        };
+        lengths.extend((0..repeat).map(|_| length));
+    }
+    if lengths.len() > literal_count + distance_count {
+        return Err(String::from("the code lengths overflow"));
+    }
+    let (literals, distances) = lengths.split_at(literal_count);
+    Ok((Huffman::new(literals), Huffman::new(distances)))
+}
+
+/// Reads bits starting with the least significant bit of each byte.
+struct Bits<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u32,
+    count: u8,
+}
+
+impl<'a> Bits<'a> {
+    fn new(data: &'a [u8]) -> Bits<'a> {
+        Bits {
+            data,
+            position: 0,
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    fn read(&mut self, count: u8) -> Result<u32, String> {
+        while self.count < count {
+            let byte = self
+                .data
+                .get(self.position)
+                .ok_or("the compressed data ends early")?;
+            self.buffer |= (*byte as u32) << self.count;
+            self.position += 1;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1 << count) - 1);
+        self.buffer >>= count;
+        self.count -= count;
+        Ok(value)
+    }
+
+    /// Skips the rest of the current byte.
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+/// A canonical Huffman code given by the code length of each symbol.
+struct Huffman {
+    /// The number of codes of each length.
+    counts: [u16; 16],
+    /// The symbols ordered by their codes.
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0; 16];
+        for length in lengths {
+            counts[*length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut symbols: Vec<u16> = (0..lengths.len() as u16)
+            .filter(|symbol| lengths[*symbol as usize] > 0)
+            .collect();
+        symbols.sort_by_key(|symbol| lengths[*symbol as usize]);
+        Huffman { counts, symbols }
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u16, String> {
+        // The codes of one length are consecutive numbers, starting at
+        // `first`, and the codes are read most significant bit first.
+        let mut code = 0;
+        let mut first = 0;
+        let mut index = 0;
+        for count in &self.counts[1..] {
+            code |= bits.read(1)? as i32;
+            let count = *count as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(String::from("invalid Huffman code"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inflate_compressed_blocks() {
+        // zlib.compress(b"hello hello hello!", 9), using the fixed codes.
+        let fixed = [
+            0x78, 0xda, 0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0x40, 0x90, 0x8a, 0x00, 0x40,
+            0xcc, 0x06, 0x9e,
+        ];
+        assert_eq!(zlib(&fixed, 18).unwrap(), b"hello hello hello!");
+
+        // Runs of letters, using codes stored in the block.
+        let expected: Vec<u8> = (0..200)
+            .flat_map(|i| vec![i as u8 % 7 + b'a'; i % 5 + 1])
+            .chain(*b"the end")
+            .collect();
+        let dynamic = [
+            0x78, 0xda, 0xed, 0x8d, 0xb9, 0x0d, 0xc0, 0x30, 0x10, 0xc3, 0x56, 0xc9, 0x6a, 0xba,
+            0x37, 0x95, 0x2b, 0xef, 0x8f, 0xc8, 0xc2, 0x6d, 0xe1, 0xa8, 0x24, 0x48, 0x08, 0x66,
+            0xee, 0x1e, 0x5c, 0x9e, 0x55, 0x37, 0x00, 0x33, 0x51, 0x72, 0x92, 0x22, 0x13, 0x25,
+            0x97, 0x29, 0xef, 0xac, 0x65, 0xfa, 0xf4, 0x91, 0x32, 0x31, 0xbd, 0xb9, 0xcc, 0x9a,
+            0xbe, 0xf1, 0x1f, 0xdd, 0x74, 0xb4, 0xdf, 0x7c, 0x72, 0xc5, 0x07, 0x87, 0x76, 0xec,
+            0xe8,
+        ];
+        assert_eq!(zlib(&dynamic, expected.len()).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_inflate_errors() {
+        assert_eq!(
+            zlib(&[0x78], 1).unwrap_err(),
+            "the compressed data is empty"
+        );
+        assert_eq!(zlib(&[0x78, 0x9d], 1).unwrap_err(), "invalid zlib header");
+        assert_eq!(
+            zlib(&[0x78, 0x9c, 0xcb], 1).unwrap_err(),
+            "the compressed data ends early"
+        );
+        // zlib.compress(b"a" * 1000), far more than expected.
+        assert_eq!(
+            zlib(
+                &[
+                    0x78, 0x9c, 0x4b, 0x4c, 0x1c, 0x05, 0xa3, 0x60, 0x14, 0x0c, 0x77, 0x00, 0x00,
+                    0xf9, 0xd8, 0x7a, 0xf8
+                ],
+                10
+            )
+            .unwrap_err(),
+            "the compressed data is longer than expected"
+        );
+    }
+}
@@ -0,0 +1,580 @@
+use std::f32::consts::PI;
+
+use super::{check_size, Image};
+
+/// The position in an 8 × 8 block of each coefficient in the order they are
+/// stored.
+const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20,
+    13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59,
+    52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+struct Component {
+    id: u8,
+    horizontal: usize,
+    vertical: usize,
+    quantization: usize,
+    dc_table: usize,
+    ac_table: usize,
+    /// The DC coefficient of the previous block, which the next one is
+    /// stored relative to.
+    dc: i32,
+    /// The decoded samples, padded to whole MCUs.
+    samples: Vec<u8>,
+    stride: usize,
+}
+
+/// The image a JPEG describes, filled in while reading its segments.
+struct Frame {
+    width: usize,
+    height: usize,
+    components: Vec<Component>,
+    max_horizontal: usize,
+    max_vertical: usize,
+}
+
+/// Decodes a baseline JPEG.
+pub fn decode(data: &[u8]) -> Result<Image, String> {
+    let mut quantization = [[0u16; 64]; 4];
+    let mut dc_tables: [Option<Huffman>; 4] = Default::default();
+    let mut ac_tables: [Option<Huffman>; 4] = Default::default();
+    let mut frame: Option<Frame> = None;
+    let mut restart_interval = 0;
+    let mut position = 2;
+    loop {
+        // Markers may be preceded by any number of fill bytes.
+        while data.get(position) == Some(&0xff) && data.get(position + 1) == Some(&0xff) {
+            position += 1;
+        }
+        let (Some(0xff), Some(marker)) = (data.get(position), data.get(position + 1)) else {
+            return Err(String::from("the file ends before the image"));
+        };
+        let marker = *marker;
+        position += 2;
+        if marker == 0xd9 {
+            break;
+        }
+        if (0xd0..=0xd7).contains(&marker) || marker == 0x01 {
+            continue;
+        }
+        let length = data
+            .get(position..position + 2)
+            .map(|length| u16::from_be_bytes([length[0], length[1]]) as usize)
+            .filter(|length| *length >= 2)
+            .ok_or("invalid segment length")?;
+        let segment = data
+            .get(position + 2..position + length)
+            .ok_or("the file ends inside a segment")?;
+        position += length;
+        match marker {
+            0xdb => read_quantization_tables(segment, &mut quantization)?,
+            0xc4 => read_huffman_tables(segment, &mut dc_tables, &mut ac_tables)?,
+            0xc0 | 0xc1 => frame = Some(read_frame(segment)?),
+            0xc2 | 0xc6 | 0xca | 0xce => {
+                return Err(String::from("progressive JPEGs are not supported"))
+            }
+            0xc3 | 0xc5 | 0xc7 | 0xc9 | 0xcb | 0xcd | 0xcf => {
+                return Err(String::from("only baseline JPEGs are supported"))
+            }
+            0xdd => {
+                restart_interval = segment
+                    .get(..2)
+                    .map(|interval| u16::from_be_bytes([interval[0], interval[1]]) as usize)
+                    .ok_or("the DRI segment is too short")?
+            }
+            0xda => {
+                let frame = frame
+                    .as_mut()
+                    .ok_or("a scan comes before the frame header")?;
+                let scan = read_scan_header(segment, frame)?;
+                let mut bits = Bits::new(&data[position..]);
+                let tables = Tables {
+                    quantization: &quantization,
+                    dc: &dc_tables,
+                    ac: &ac_tables,
+                };
+                decode_scan(frame, &scan, &tables, restart_interval, &mut bits)?;
+                position += bits.position;
+            }
+            _ => {}
+        }
+    }
+    let frame = frame.ok_or("the frame header is missing")?;
+    to_image(&frame)
+}
+
+fn read_quantization_tables(mut segment: &[u8], tables: &mut [[u16; 64]; 4]) -> Result<(), String> {
+    while let Some((info, rest)) = segment.split_first() {
+        let table = tables
+            .get_mut((info & 0x0f) as usize)
+            .ok_or("invalid quantization table")?;
+        let wide = info >> 4 == 1;
+        let size = if wide { 128 } else { 64 };
+        let values = rest.get(..size).ok_or("the DQT segment is too short")?;
+        for (i, value) in table.iter_mut().enumerate() {
+            *value = match wide {
+                true => u16::from_be_bytes([values[i * 2], values[i * 2 + 1]]),
+                false => values[i] as u16,
+            };
+        }
+        segment = &rest[size..];
+    }
+    Ok(())
+}
+
+fn read_huffman_tables(
+    mut segment: &[u8],
+    dc_tables: &mut [Option<Huffman>; 4],
+    ac_tables: &mut [Option<Huffman>; 4],
+) -> Result<(), String> {
+    while let Some((info, rest)) = segment.split_first() {
+        let counts = rest.get(..16).ok_or("the DHT segment is too short")?;
+        let total: usize = counts.iter().map(|count| *count as usize).sum();
+        let symbols = rest
+            .get(16..16 + total)
+            .ok_or("the DHT segment is too short")?;
+        let tables = match info >> 4 {
+            0 => &mut *dc_tables,
+            _ => &mut *ac_tables,
+        };
+        let table = tables
+            .get_mut((info & 0x0f) as usize)
+            .ok_or("invalid Huffman table")?;
+        *table = Some(Huffman::new(counts, symbols));
+        segment = &rest[16 + total..];
+    }
+    Ok(())
+}
+
+fn read_frame(segment: &[u8]) -> Result<Frame, String> {
+    if segment.len() < 6 {
+        return Err(String::from("the frame header is too short"));
+    }
+    if segment[0] != 8 {
+        return Err(format!("{}-bit JPEGs are not supported", segment[0]));
+    }
+    let height = u16::from_be_bytes([segment[1], segment[2]]) as usize;
+    let width = u16::from_be_bytes([segment[3], segment[4]]) as usize;
+    check_size(width, height)?;
+    let count = segment[5] as usize;
+    if count != 1 && count != 3 {
+        return Err(format!("JPEGs with {} components are not supported", count));
+    }
+    let mut components = segment[6..]
+        .chunks_exact(3)
+        .take(count)
+        .map(|component| Component {
+            id: component[0],
+            horizontal: (component[1] >> 4).clamp(1, 4) as usize,
+            vertical: (component[1] & 0x0f).clamp(1, 4) as usize,
+            quantization: (component[2] & 3) as usize,
+            dc_table: 0,
+            ac_table: 0,
+            dc: 0,
+            samples: Vec::new(),
+            stride: 0,
+        })
+        .collect::<Vec<_>>();
+    if components.len() < count {
+        return Err(String::from("the frame header is too short"));
+    }
+    let max_horizontal = components.iter().map(|c| c.horizontal).max().unwrap();
+    let max_vertical = components.iter().map(|c| c.vertical).max().unwrap();
+    let mcu_columns = width.div_ceil(8 * max_horizontal);
+    let mcu_rows = height.div_ceil(8 * max_vertical);
+    for component in &mut components {
+        component.stride = mcu_columns * component.horizontal * 8;
+        component.samples = vec![0; component.stride * mcu_rows * component.vertical * 8];
+    }
+    Ok(Frame {
+        width,
+        height,
+        components,
+        max_horizontal,
+        max_vertical,
+    })
+}
+
+/// The indices of the components in a scan.
+fn read_scan_header(segment: &[u8], frame: &mut Frame) -> Result<Vec<usize>, String> {
+    let count = *segment.first().ok_or("the scan header is too short")? as usize;
+    let mut scan = Vec::new();
+    for selector in segment[1..].chunks_exact(2).take(count) {
+        let index = frame
+            .components
+            .iter()
+            .position(|component| component.id == selector[0])
+            .ok_or("a scan refers to an unknown component")?;
+        let component = &mut frame.components[index];
+        component.dc_table = (selector[1] >> 4 & 3) as usize;
+        component.ac_table = (selector[1] & 3) as usize;
+        scan.push(index);
+    }
+    if scan.len() < count || scan.is_empty() {
+        return Err(String::from("the scan header is too short"));
+    }
+    Ok(scan)
+}
+
+struct Tables<'a> {
+    quantization: &'a [[u16; 64]; 4],
+    dc: &'a [Option<Huffman>; 4],
+    ac: &'a [Option<Huffman>; 4],
+}
+
+fn decode_scan(
+    frame: &mut Frame,
+    scan: &[usize],
+    tables: &Tables,
+    restart_interval: usize,
+    bits: &mut Bits,
+) -> Result<(), String> {
+    // A scan of one component stores its blocks one by one, several
+    // components are interleaved in MCUs.
+    let (columns, rows) = match scan {
+        [index] => {
+            let component = &frame.components[*index];
+            (
+                (frame.width * component.horizontal).div_ceil(8 * frame.max_horizontal),
+                (frame.height * component.vertical).div_ceil(8 * frame.max_vertical),
+            )
+        }
+        _ => (
+            frame.width.div_ceil(8 * frame.max_horizontal),
+            frame.height.div_ceil(8 * frame.max_vertical),
+        ),
+    };
+    for component in &mut frame.components {
+        component.dc = 0;
+    }
+    let mut block = [0i32; 64];
+    for mcu in 0..columns * rows {
+        if restart_interval > 0 && mcu > 0 && mcu % restart_interval == 0 {
+            bits.restart();
+            for component in &mut frame.components {
+                component.dc = 0;
+            }
+        }
+        let (mcu_column, mcu_row) = (mcu % columns, mcu / columns);
+        for index in scan {
+            let component = &mut frame.components[*index];
+            let (horizontal, vertical) = match scan.len() {
+                1 => (1, 1),
+                _ => (component.horizontal, component.vertical),
+            };
+            for v in 0..vertical {
+                for h in 0..horizontal {
+                    decode_block(component, tables, bits, &mut block)?;
+                    let x = (mcu_column * horizontal + h) * 8;
+                    let y = (mcu_row * vertical + v) * 8;
+                    idct(
+                        &block,
+                        &mut component.samples[y * component.stride + x..],
+                        component.stride,
+                    );
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn decode_block(
+    component: &mut Component,
+    tables: &Tables,
+    bits: &mut Bits,
+    block: &mut [i32; 64],
+) -> Result<(), String> {
+    let quantization = &tables.quantization[component.quantization];
+    let dc_table = tables.dc[component.dc_table]
+        .as_ref()
+        .ok_or("a scan uses a missing Huffman table")?;
+    let ac_table = tables.ac[component.ac_table]
+        .as_ref()
+        .ok_or("a scan uses a missing Huffman table")?;
+    block.fill(0);
+    let size = dc_table.decode(bits)?;
+    component.dc = component.dc.wrapping_add(bits.receive(size)?);
+    block[0] = component.dc.saturating_mul(quantization[0] as i32);
+    let mut k = 1;
+    while k < 64 {
+        let symbol = ac_table.decode(bits)?;
+        let (zeros, size) = ((symbol >> 4) as usize, symbol & 0x0f);
+        if size == 0 {
+            if zeros != 15 {
+                // The rest of the block is zero.
+                break;
+            }
+            k += 16;
+            continue;
+        }
+        k += zeros;
+        if k > 63 {
+            return Err(String::from("a block has too many coefficients"));
+        }
+        block[ZIGZAG[k]] = bits.receive(size)? * quantization[k] as i32;
+        k += 1;
+    }
+    Ok(())
+}
+
+/// Turns the coefficients of a block into samples, written `stride` apart
+/// into `output`.
+fn idct(block: &[i32; 64], output: &mut [u8], stride: usize) {
+    // cos((2x + 1)uπ / 16) scaled by C(u) / 2, with C(0) = 1 / √2.
+    let cosines: [[f32; 8]; 8] = std::array::from_fn(|x| {
+        std::array::from_fn(|u| {
+            let scale = if u == 0 { 0.5 / 2f32.sqrt() } else { 0.5 };
+            scale * ((2 * x + 1) as f32 * u as f32 * PI / 16.0).cos()
+        })
+    });
+    let mut rows = [[0f32; 8]; 8];
+    for v in 0..8 {
+        for x in 0..8 {
+            rows[v][x] = (0..8)
+                .map(|u| cosines[x][u] * block[v * 8 + u] as f32)
+                .sum();
+        }
+    }
+    for y in 0..8 {
+        for x in 0..8 {
+            let value: f32 = (0..8).map(|v| cosines[y][v] * rows[v][x]).sum();
+            output[y * stride + x] = (value + 128.0).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+fn to_image(frame: &Frame) -> Result<Image, String> {
+    let mut pixels = Vec::with_capacity(frame.width * frame.height);
+    for y in 0..frame.height {
+        for x in 0..frame.width {
+            let sample = |component: &Component| {
+                let x = x * component.horizontal / frame.max_horizontal;
+                let y = y * component.vertical / frame.max_vertical;
+                component.samples[y * component.stride + x] as f32
+            };
+            pixels.push(match &frame.components[..] {
+                [grey] => {
+                    let grey = sample(grey) as u8;
+                    [grey, grey, grey, 255]
+                }
+                [luma, blue, red] => {
+                    let (luma, blue, red) =
+                        (sample(luma), sample(blue) - 128.0, sample(red) - 128.0);
+                    let channel = |value: f32| value.round().clamp(0.0, 255.0) as u8;
+                    [
+                        channel(luma + 1.402 * red),
+                        channel(luma - 0.344136 * blue - 0.714136 * red),
+                        channel(luma + 1.772 * blue),
+                        255,
+                    ]
+                }
+                _ => return Err(String::from("unsupported components")),
+            });
+        }
+    }
+    Ok(Image {
+        width: frame.width,
+        height: frame.height,
+        pixels,
+    })
+}
+
+/// A Huffman table given by the number of codes of each length and the
+/// symbols ordered by their codes.
+struct Huffman {
+    counts: [u8; 16],
+    symbols: Vec<u8>,
+}
+
+impl Huffman {
+    fn new(counts: &[u8], symbols: &[u8]) -> Huffman {
+        Huffman {
+            counts: counts.try_into().unwrap(),
+            symbols: symbols.to_vec(),
+        }
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u8, String> {
+        let mut code = 0;
+        let mut first = 0;
+        let mut index = 0;
+        for count in self.counts {
+            code |= bits.read(1) as i32;
+            let count = count as i32;
+            if code - first < count {
+                return self
+                    .symbols
+                    .get((index + code - first) as usize)
+                    .copied()
+                    .ok_or_else(|| String::from("invalid Huffman code"));
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(String::from("invalid Huffman code"))
+    }
+}
+
+/// Reads entropy coded data most significant bit first, skipping the zero
+/// bytes stuffed after `0xff`.
+struct Bits<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u32,
+    count: u8,
+}
+
+impl<'a> Bits<'a> {
+    fn new(data: &'a [u8]) -> Bits<'a> {
+        Bits {
+            data,
+            position: 0,
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    fn read(&mut self, count: u8) -> u32 {
+        while self.count < count {
+            let byte = match (
+                self.data.get(self.position),
+                self.data.get(self.position + 1),
+            ) {
+                (Some(0xff), Some(0x00)) => {
+                    self.position += 2;
+                    0xff
+                }
+                // A marker ends the data, the missing bits read as zero.
+                (Some(0xff), _) | (None, _) => 0,
+                (Some(byte), _) => {
+                    self.position += 1;
+                    *byte
+                }
+            };
+            self.buffer = (self.buffer << 8) | byte as u32;
+            self.count += 8;
+        }
+        self.count -= count;
+        (self.buffer >> self.count) & ((1 << count) - 1)
+    }
+
+    /// Reads a coefficient of `size` bits, where values with the top bit
+    /// clear are negative. The size comes from the file, coefficients are at
+    /// most 15 bits long.
+    fn receive(&mut self, size: u8) -> Result<i32, String> {
+        if size == 0 {
+            return Ok(0);
+        }
+        if size > 15 {
+            return Err(format!("invalid coefficient size {}", size));
+        }
+        let value = self.read(size) as i32;
+        Ok(match value < 1 << (size - 1) {
+            true => value - (1 << size) + 1,
+            false => value,
+        })
+    }
+
+    /// Skips to the byte after the next restart marker.
+    fn restart(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+        if let Some([0xff, 0xd0..=0xd7]) = self.data.get(self.position..self.position + 2) {
+            self.position += 2;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_subsampled_jpeg_with_restarts() {
+        // 32 × 16 pixels, red on the left and blue on the right, with
+        // subsampled colours and a restart marker after each MCU.
+        let jpeg = [
+            0xff, 0xd8, 0xff, 0xdb, 0x00, 0x43, 0x00, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08,
+            0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08,
+            0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08,
+            0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08,
+            0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08,
+            0x08, 0xff, 0xc0, 0x00, 0x11, 0x08, 0x00, 0x10, 0x00, 0x20, 0x03, 0x01, 0x22, 0x00,
+            0x02, 0x11, 0x00, 0x03, 0x11, 0x00, 0xff, 0xc4, 0x00, 0x16, 0x00, 0x00, 0x03, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x06, 0x07, 0xff, 0xc4, 0x00, 0x14, 0x10, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0xc4, 0x00, 0x16,
+            0x01, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x05, 0x06, 0x07, 0xff, 0xc4, 0x00, 0x14, 0x11, 0x01, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0xff, 0xdd, 0x00, 0x04, 0x00, 0x01, 0xff, 0xda, 0x00, 0x0c, 0x03, 0x01, 0x00, 0x02,
+            0x11, 0x03, 0x11, 0x00, 0x3f, 0x00, 0x4b, 0x00, 0x15, 0x17, 0xf7, 0xff, 0xd0, 0x8e,
+            0x00, 0x17, 0xf0, 0xa7, 0xff, 0xd9,
+        ];
+        let image = decode(&jpeg).unwrap();
+        assert_eq!((image.width, image.height), (32, 16));
+        let close = |[r, g, b, _]: [u8; 4], expected: [u8; 3]| {
+            [r, g, b]
+                .iter()
+                .zip(expected)
+                .all(|(value, expected)| value.abs_diff(expected) <= 8)
+        };
+        assert!(close(image.pixel(3, 5), [255, 0, 0]));
+        assert!(close(image.pixel(28, 12), [0, 0, 255]));
+
+        // Cut off or corrupted, the image may fail to decode but never panics.
+        for length in 0..jpeg.len() {
+            let _ = decode(&jpeg[..length]);
+        }
+        assert_eq!(jpeg[72], 0xc0);
+        // All but the height and width, which only make the image larger.
+        for position in (0..jpeg.len()).filter(|position| !(76..80).contains(position)) {
+            for value in [0x00, 0x7f, 0xff] {
+                let mut corrupt = jpeg;
+                corrupt[position] = value;
+                let _ = decode(&corrupt);
+            }
+        }
+    }
+
+    #[test]
+    fn test_malformed_jpegs_are_rejected() {
+        let frame = |height: u16, width: u16| {
+            let mut jpeg = vec![0xff, 0xd8, 0xff, 0xc0, 0x00, 0x0b, 0x08];
+            jpeg.extend(height.to_be_bytes());
+            jpeg.extend(width.to_be_bytes());
+            jpeg.extend([0x01, 0x01, 0x11, 0x00]);
+            jpeg
+        };
+        let mut jpeg = frame(0xffff, 0xffff);
+        jpeg.extend([0xff, 0xd9]);
+        assert_eq!(
+            decode(&jpeg).unwrap_err(),
+            "the image is too large (65535 × 65535 pixels)"
+        );
+
+        // A DC table whose only symbol is a size of 32 bits.
+        let mut jpeg = frame(8, 8);
+        jpeg.extend([0xff, 0xc4, 0x00, 0x14, 0x00, 0x01]);
+        jpeg.extend([0; 15]);
+        jpeg.push(0x20);
+        jpeg.extend([0xff, 0xc4, 0x00, 0x14, 0x10, 0x01]);
+        jpeg.extend([0; 15]);
+        jpeg.push(0x00);
+        jpeg.extend([0xff, 0xda, 0x00, 0x08, 0x01, 0x01, 0x00, 0x00, 0x3f, 0x00]);
+        jpeg.extend([0x00, 0xff, 0xd9]);
+        assert_eq!(decode(&jpeg).unwrap_err(), "invalid coefficient size 32");
+    }
+
+    #[test]
+    fn test_progressive_jpegs_are_rejected() {
+        let jpeg = [0xff, 0xd8, 0xff, 0xc2, 0x00, 0x02, 0xff, 0xd9];
+        assert_eq!(
+            decode(&jpeg).unwrap_err(),
+            "progressive JPEGs are not supported"
+        );
+    }
+}
@@ -0,0 +1,335 @@
+use super::{check_size, inflate, Image};
+
+pub const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// The first column and row and the spacing of the pixels in each of the
+/// seven passes of an interlaced image.
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+struct Header {
+    width: usize,
+    height: usize,
+    bit_depth: u8,
+    color_type: u8,
+    interlaced: bool,
+}
+
+pub fn decode(data: &[u8]) -> Result<Image, String> {
+    let mut header = None;
+    let mut palette: Vec<[u8; 4]> = Vec::new();
+    // The alpha of palette entries, or the sample values of a transparent
+    // grey or RGB colour.
+    let mut transparency: Option<&[u8]> = None;
+    let mut compressed = Vec::new();
+    let mut rest = &data[SIGNATURE.len()..];
+    loop {
+        if rest.len() < 12 {
+            return Err(String::from("the file ends before the IEND chunk"));
+        }
+        let length = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        let kind = &rest[4..8];
+        let body = rest
+            .get(8..8 + length)
+            .ok_or("the file ends inside a chunk")?;
+        match kind {
+            b"IHDR" => header = Some(parse_header(body)?),
+            b"PLTE" => {
+                palette = body
+                    .chunks_exact(3)
+                    .map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+                    .collect()
+            }
+            b"tRNS" => transparency = Some(body),
+            b"IDAT" => compressed.extend_from_slice(body),
+            b"IEND" => break,
+            _ => {}
+        }
+        rest = rest.get(12 + length..).unwrap_or_default();
+    }
+    let header = header.ok_or("the IHDR chunk is missing")?;
+    if header.color_type == 3 {
+        for (entry, alpha) in palette.iter_mut().zip(transparency.unwrap_or_default()) {
+            entry[3] = *alpha;
+        }
+    }
+    let channels = match header.color_type {
+        0 | 3 => 1,
+        2 => 3,
+        4 => 2,
+        6 => 4,
+        color_type => return Err(format!("unknown color type {}", color_type)),
+    };
+    let valid_depth = match header.color_type {
+        0 => [1, 2, 4, 8, 16].contains(&header.bit_depth),
+        3 => [1, 2, 4, 8].contains(&header.bit_depth),
+        _ => [8, 16].contains(&header.bit_depth),
+    };
+    if !valid_depth {
+        return Err(format!(
+            "invalid bit depth {} for color type {}",
+            header.bit_depth, header.color_type
+        ));
+    }
+    let bits_per_pixel = channels * header.bit_depth as usize;
+    // Filters work on bytes, comparing each with the one of the pixel before.
+    let filter_distance = (bits_per_pixel / 8).max(1);
+    let passes = match header.interlaced {
+        true => &ADAM7[..],
+        false => &[(0, 0, 1, 1)],
+    };
+    // Each row of each pass starts with the byte of its filter type.
+    let raw_length = passes
+        .iter()
+        .map(|(left, top, dx, dy)| {
+            let columns = header.width.saturating_sub(*left).div_ceil(*dx);
+            let rows = header.height.saturating_sub(*top).div_ceil(*dy);
+            match columns {
+                0 => 0,
+                _ => rows * ((columns * bits_per_pixel).div_ceil(8) + 1),
+            }
+        })
+        .sum();
+    let raw = inflate::zlib(&compressed, raw_length)?;
+    let transparent_color = transparency.filter(|_| header.color_type != 3);
+    let mut pixels = vec![[0; 4]; header.width * header.height];
+    let mut rest = &raw[..];
+    for (left, top, dx, dy) in passes {
+        let columns = header.width.saturating_sub(*left).div_ceil(*dx);
+        let rows = header.height.saturating_sub(*top).div_ceil(*dy);
+        if columns == 0 {
+            continue;
+        }
+        let row_length = (columns * bits_per_pixel).div_ceil(8);
+        let mut previous = vec![0; row_length];
+        for row in 0..rows {
+            if rest.len() < row_length + 1 {
+                return Err(String::from("the image data ends early"));
+            }
+            let filter = rest[0];
+            let mut line = rest[1..row_length + 1].to_vec();
+            rest = &rest[row_length + 1..];
+            unfilter(filter, &mut line, &previous, filter_distance)?;
+            for column in 0..columns {
+                let samples: Vec<u16> = (0..channels)
+                    .map(|channel| sample(&line, column * channels + channel, header.bit_depth))
+                    .collect();
+                let pixel = match header.color_type {
+                    3 => *palette
+                        .get(samples[0] as usize)
+                        .ok_or("a palette index is out of range")?,
+                    _ => to_rgba(&samples, header.bit_depth, transparent_color),
+                };
+                pixels[(top + row * dy) * header.width + left + column * dx] = pixel;
+            }
+            previous = line;
+        }
+    }
+    Ok(Image {
+        width: header.width,
+        height: header.height,
+        pixels,
+    })
+}
+
+fn parse_header(body: &[u8]) -> Result<Header, String> {
+    if body.len() < 13 {
+        return Err(String::from("the IHDR chunk is too short"));
+    }
+    let number = |start: usize| u32::from_be_bytes(body[start..start + 4].try_into().unwrap());
+    let (width, height) = (number(0) as usize, number(4) as usize);
+    check_size(width, height)?;
+    Ok(Header {
+        width,
+        height,
+        bit_depth: body[8],
+        color_type: body[9],
+        interlaced: body[12] == 1,
+    })
+}
+
+/// Reverses the filter a row was stored with.
+fn unfilter(filter: u8, line: &mut [u8], previous: &[u8], distance: usize) -> Result<(), String> {
+    for i in 0..line.len() {
+        let left = if i >= distance { line[i - distance] } else { 0 };
+        let up = previous[i];
+        let up_left = if i >= distance {
+            previous[i - distance]
+        } else {
+            0
+        };
+        let prediction = match filter {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((left as u16 + up as u16) / 2) as u8,
+            4 => paeth(left, up, up_left),
+            filter => return Err(format!("unknown filter type {}", filter)),
+        };
+        line[i] = line[i].wrapping_add(prediction);
+    }
+    Ok(())
+}
+
+/// Whichever of the neighbours is closest to `left + up - up_left`.
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - up_left as i16;
+    let distance = |value: u8| (estimate - value as i16).abs();
+    if distance(left) <= distance(up) && distance(left) <= distance(up_left) {
+        left
+    } else if distance(up) <= distance(up_left) {
+        up
+    } else {
+        up_left
+    }
+}
+
+/// The sample at `index` in a row of samples `bit_depth` bits long.
+fn sample(line: &[u8], index: usize, bit_depth: u8) -> u16 {
+    match bit_depth {
+        16 => u16::from_be_bytes([line[index * 2], line[index * 2 + 1]]),
+        8 => line[index] as u16,
+        _ => {
+            let bit = index * bit_depth as usize;
+            let shift = 8 - bit_depth as usize - bit % 8;
+            (line[bit / 8] as u16 >> shift) & ((1 << bit_depth) - 1)
+        }
+    }
+}
+
+/// Converts grey or RGB samples, with or without alpha, to a pixel.
+fn to_rgba(samples: &[u16], bit_depth: u8, transparent_color: Option<&[u8]>) -> [u8; 4] {
+    let max = (1u32 << bit_depth) - 1;
+    let scale = |sample: u16| (sample as u32 * 255 / max) as u8;
+    let transparent = transparent_color.is_some_and(|color| {
+        color
+            .chunks_exact(2)
+            .map(|value| u16::from_be_bytes([value[0], value[1]]))
+            .eq(samples.iter().copied())
+    });
+    match samples {
+        [grey] => [
+            scale(*grey),
+            scale(*grey),
+            scale(*grey),
+            if transparent { 0 } else { 255 },
+        ],
+        [grey, alpha] => [scale(*grey), scale(*grey), scale(*grey), scale(*alpha)],
+        [red, green, blue] => [
+            scale(*red),
+            scale(*green),
+            scale(*blue),
+            if transparent { 0 } else { 255 },
+        ],
+        [red, green, blue, alpha, ..] => [scale(*red), scale(*green), scale(*blue), scale(*alpha)],
+        [] => [0; 4],
+    }
+}
+
+/// Encodes `image` as an RGBA PNG with uncompressed deflate blocks.
+pub fn encode(image: &Image) -> Vec<u8> {
+    let mut raw = Vec::with_capacity(image.height * (image.width * 4 + 1));
+    for row in image.pixels.chunks_exact(image.width) {
+        raw.push(0);
+        raw.extend(row.iter().flatten());
+    }
+    let mut compressed = vec![0x78, 0x01];
+    let blocks = raw.chunks(0xffff);
+    let count = blocks.len();
+    for (i, block) in blocks.enumerate() {
+        compressed.push(u8::from(i + 1 == count));
+        let length = block.len() as u16;
+        compressed.extend(length.to_le_bytes());
+        compressed.extend((!length).to_le_bytes());
+        compressed.extend(block);
+    }
+    compressed.extend(adler32(&raw).to_be_bytes());
+
+    let mut header = Vec::with_capacity(13);
+    header.extend((image.width as u32).to_be_bytes());
+    header.extend((image.height as u32).to_be_bytes());
+    header.extend([8, 6, 0, 0, 0]);
+    let mut png = SIGNATURE.to_vec();
+    for (kind, body) in [
+        (b"IHDR", &header[..]),
+        (b"IDAT", &compressed[..]),
+        (b"IEND", &[][..]),
+    ] {
+        png.extend((body.len() as u32).to_be_bytes());
+        let start = png.len();
+        png.extend(kind);
+        png.extend(body);
+        let crc = crc32(&png[start..]);
+        png.extend(crc.to_be_bytes());
+    }
+    png
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb88320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_filtered_palette_image() {
+        // A 3 × 2 image with a 2-bit palette, the second entry transparent
+        // and the second row stored with the `Up` filter.
+        let png = [
+            0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48,
+            0x44, 0x52, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x02, 0x02, 0x03, 0x00, 0x00,
+            0x00, 0xe0, 0x1a, 0x8e, 0x89, 0x00, 0x00, 0x00, 0x09, 0x50, 0x4c, 0x54, 0x45, 0xff,
+            0x00, 0x00, 0x00, 0xff, 0x00, 0x00, 0x00, 0xff, 0x2d, 0x4a, 0xcd, 0x8a, 0x00, 0x00,
+            0x00, 0x02, 0x74, 0x52, 0x4e, 0x53, 0xff, 0x00, 0xe5, 0xb7, 0x30, 0x4a, 0x00, 0x00,
+            0x00, 0x0c, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0x90, 0x60, 0xaa, 0x00, 0x00,
+            0x00, 0xc8, 0x00, 0x93, 0x8e, 0x38, 0xa6, 0x46, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45,
+            0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+        ];
+        let image = decode(&png).unwrap();
+        let (red, green, blue) = ([255, 0, 0, 255], [0, 255, 0, 0], [0, 0, 255, 255]);
+        assert_eq!(image.pixels, vec![red, green, blue, blue, green, red]);
+    }
+
+    #[test]
+    fn test_reject_images_too_large_to_decode() {
+        let mut png = Image::from_fn(1, 1, |_, _| [0; 4]).to_png();
+        // The width in the IHDR chunk, 4 billion pixels.
+        png[16..20].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert_eq!(
+            decode(&png).unwrap_err(),
+            "the image is too large (4294967295 × 1 pixels)"
+        );
+    }
+
+    #[test]
+    fn test_paeth() {
+        assert_eq!(paeth(10, 20, 10), 20);
+        assert_eq!(paeth(20, 10, 10), 20);
+        assert_eq!(paeth(10, 10, 30), 10);
+    }
+}
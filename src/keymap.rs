@@ -0,0 +1,439 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use serde::Deserialize;
+use termion::event::Key;
+
+/// Something a key binding does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// The next reveal step or slide.
+    Next,
+    /// The previous reveal step or slide.
+    Previous,
+    /// The first slide, or the slide given by the count.
+    FirstSlide,
+    /// The last slide, or the slide given by the count.
+    LastSlide,
+    /// Asks for the number of the slide to go to.
+    GoToSlide,
+    /// Shows all slides as a grid to pick one from.
+    Overview,
+    /// Asks for text to search the slides for.
+    Search,
+    /// The next slide with the text searched for.
+    SearchNext,
+    /// The previous slide with the text searched for.
+    SearchPrevious,
+    /// Removes the highlighting of the text searched for.
+    ClearSearch,
+    /// Back to where the last jump to another slide started.
+    JumpBack,
+    /// Forward again to where `JumpBack` came from.
+    JumpForward,
+    CycleTheme,
+    Quit,
+}
+
+/// The bindings used unless the config file changes them, in the notation of
+/// the config file.
+const DEFAULT_BINDINGS: &[(Action, &[&str])] = &[
+    (
+        Action::Next,
+        &[
+            "l",
+            "j",
+            "<Right>",
+            "<Down>",
+            "<Space>",
+            "<Enter>",
+            "<PageDown>",
+        ],
+    ),
+    (
+        Action::Previous,
+        &["h", "k", "<Left>", "<Up>", "<Backspace>", "<PageUp>"],
+    ),
+    (Action::FirstSlide, &["gg", "<Home>"]),
+    (Action::LastSlide, &["G", "<End>"]),
+    (Action::GoToSlide, &[":"]),
+    (Action::Overview, &["o"]),
+    (Action::Search, &["/"]),
+    (Action::SearchNext, &["n"]),
+    (Action::SearchPrevious, &["N"]),
+    (Action::ClearSearch, &["<Esc>"]),
+    // Ctrl-i can't be told apart from Tab.
+    (Action::JumpBack, &["<C-o>"]),
+    (Action::JumpForward, &["<Tab>"]),
+    (Action::CycleTheme, &["t"]),
+    (Action::Quit, &["q", "<C-c>"]),
+];
+
+/// An action with the count typed before its keys, like the 5 of `5l`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Command {
+    pub action: Action,
+    pub count: Option<usize>,
+}
+
+/// Maps sequences of keys to actions, collecting the keys of a sequence as
+/// they are pressed.
+#[derive(Debug)]
+pub struct Keymap {
+    bindings: Vec<(Vec<Key>, Action)>,
+    /// The keys pressed so far of a sequence that isn't complete yet.
+    pending: Vec<Key>,
+    count: Option<usize>,
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        let bindings = DEFAULT_BINDINGS
+            .iter()
+            .flat_map(|(action, sequences)| {
+                sequences
+                    .iter()
+                    .map(|sequence| (parse_keys(sequence).unwrap(), *action))
+            })
+            .collect();
+        Keymap {
+            bindings,
+            pending: Vec::new(),
+            count: None,
+        }
+    }
+}
+
+impl Keymap {
+    /// Loads the `[keys]` table of a config file. The keys it lists for an
+    /// action replace that action's default keys; the other actions keep
+    /// theirs, except for default keys that conflict with the listed ones.
+    pub fn load(path: &Path) -> Result<Keymap, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|err| format!("Error reading config {}: {}", path.display(), err))?;
+        Keymap::parse(&content)
+            .map_err(|err| format!("Error in config {}: {}", path.display(), err))
+    }
+
+    fn parse(content: &str) -> Result<Keymap, String> {
+        let file: ConfigFile = toml::from_str(content).map_err(|err| err.to_string())?;
+        let mut keymap = Keymap::default();
+        keymap
+            .bindings
+            .retain(|(_, bound)| !file.keys.contains_key(bound));
+        let mut listed: Vec<&KeySequence> = Vec::new();
+        for (action, sequences) in &file.keys {
+            for sequence in sequences {
+                // A sequence starting with another one could never be completed.
+                let conflicts = |keys: &[Key]| {
+                    keys.starts_with(&sequence.keys) || sequence.keys.starts_with(keys)
+                };
+                if let Some(other) = listed.iter().find(|other| conflicts(&other.keys)) {
+                    return Err(match other.text == sequence.text {
+                        true => format!("the keys `{}` are bound twice", sequence.text),
+                        false => format!(
+                            "the keys `{}` and `{}` conflict, one starts with the other",
+                            other.text, sequence.text
+                        ),
+                    });
+                }
+                keymap.bindings.retain(|(bound, _)| !conflicts(bound));
+                keymap.bindings.push((sequence.keys.clone(), *action));
+                listed.push(sequence);
+            }
+        }
+        Ok(keymap)
+    }
+
+    /// Adds a pressed key. Returns the action and its count once the keys
+    /// form a binding; keys that can't become one are dropped with the count.
+    pub fn press(&mut self, key: Key) -> Option<Command> {
+        match key {
+            // Like in Vim a leading 0 isn't a count.
+            Key::Char(digit @ '0'..='9')
+                if self.pending.is_empty() && (digit != '0' || self.count.is_some()) =>
+            {
+                let digit = digit.to_digit(10).unwrap() as usize;
+                let count = self.count.unwrap_or(0).saturating_mul(10);
+                self.count = Some(count.saturating_add(digit));
+                None
+            }
+            key => self.press_bound(key).map(|action| Command {
+                action,
+                count: self.count.take(),
+            }),
+        }
+    }
+
+    fn press_bound(&mut self, key: Key) -> Option<Action> {
+        self.pending.push(key);
+        if let Some((_, action)) = self.bindings.iter().find(|(keys, _)| *keys == self.pending) {
+            self.pending.clear();
+            return Some(*action);
+        }
+        if self
+            .bindings
+            .iter()
+            .any(|(keys, _)| keys.starts_with(&self.pending))
+        {
+            return None;
+        }
+        let restart = self.pending.len() > 1;
+        self.pending.clear();
+        self.count = None;
+        // The key may start a new sequence, like the second `g` of `xgg`.
+        match restart {
+            true => self.press_bound(key),
+            false => None,
+        }
+    }
+}
+
+/// What happened to a prompt after a key press.
+#[derive(Debug, PartialEq)]
+pub enum PromptInput {
+    Editing,
+    Submitted(String),
+    Cancelled,
+}
+
+/// A line of text typed at the bottom of the screen after a symbol like `:`,
+/// finished with Enter and cancelled with Esc.
+#[derive(Debug)]
+pub struct Prompt {
+    pub symbol: char,
+    pub text: String,
+}
+
+impl Prompt {
+    pub fn new(symbol: char) -> Prompt {
+        Prompt {
+            symbol,
+            text: String::new(),
+        }
+    }
+
+    pub fn press(&mut self, key: Key) -> PromptInput {
+        match key {
+            Key::Char('\n') => PromptInput::Submitted(self.text.clone()),
+            Key::Esc | Key::Ctrl('c') => PromptInput::Cancelled,
+            // Deleting past the start closes the prompt, as in Vim.
+            Key::Backspace if self.text.pop().is_none() => PromptInput::Cancelled,
+            Key::Char(c) if !c.is_control() => {
+                self.text.push(c);
+                PromptInput::Editing
+            }
+            _ => PromptInput::Editing,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    #[serde(default)]
+    keys: BTreeMap<Action, Vec<KeySequence>>,
+}
+
+/// Keys as listed in the config file, kept as written for error messages.
+#[derive(Deserialize)]
+#[serde(try_from = "String")]
+struct KeySequence {
+    text: String,
+    keys: Vec<Key>,
+}
+
+impl TryFrom<String> for KeySequence {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<KeySequence, String> {
+        let keys = parse_keys(&text)?;
+        Ok(KeySequence { text, keys })
+    }
+}
+
+/// Parses keys written like in Vim: characters stand for themselves and
+/// special keys are named in angle brackets, e.g. `gg`, `<PageDown>`, `<C-o>`.
+fn parse_keys(sequence: &str) -> Result<Vec<Key>, String> {
+    let mut keys = Vec::new();
+    let mut rest = sequence;
+    while let Some(c) = rest.chars().next() {
+        let name = rest
+            .strip_prefix('<')
+            .and_then(|after| Some(&after[..after.find('>')?]))
+            .filter(|name| !name.is_empty());
+        match name {
+            Some(name) => {
+                keys.push(parse_key_name(name).ok_or_else(|| format!("unknown key `<{}>`", name))?);
+                rest = &rest[name.len() + 2..];
+            }
+            None => {
+                keys.push(Key::Char(c));
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    if keys.is_empty() {
+        return Err(String::from("empty key sequence"));
+    }
+    Ok(keys)
+}
+
+fn parse_key_name(name: &str) -> Option<Key> {
+    let modified = |prefix: &str| {
+        let rest = name
+            .get(..2)?
+            .eq_ignore_ascii_case(prefix)
+            .then(|| &name[2..])?;
+        let mut chars = rest.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        }
+    };
+    if let Some(c) = modified("c-") {
+        return Some(Key::Ctrl(c.to_ascii_lowercase()));
+    }
+    if let Some(c) = modified("a-") {
+        return Some(Key::Alt(c));
+    }
+    let key = match name.to_ascii_lowercase().as_str() {
+        "left" => Key::Left,
+        "right" => Key::Right,
+        "up" => Key::Up,
+        "down" => Key::Down,
+        "home" => Key::Home,
+        "end" => Key::End,
+        "pageup" => Key::PageUp,
+        "pagedown" => Key::PageDown,
+        "space" => Key::Char(' '),
+        "enter" | "cr" => Key::Char('\n'),
+        "tab" => Key::Char('\t'),
+        "backspace" | "bs" => Key::Backspace,
+        "delete" | "del" => Key::Delete,
+        "insert" => Key::Insert,
+        "esc" => Key::Esc,
+        "lt" => Key::Char('<'),
+        name => match name.strip_prefix('f').map(str::parse) {
+            Some(Ok(number @ 1..=12)) => Key::F(number),
+            _ => return None,
+        },
+    };
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press_all(keymap: &mut Keymap, keys: &str) -> Vec<Action> {
+        keys.chars()
+            .filter_map(|c| keymap.press(Key::Char(c)))
+            .map(|command| command.action)
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_keys() {
+        assert_eq!(parse_keys("gg"), Ok(vec![Key::Char('g'), Key::Char('g')]));
+        assert_eq!(
+            parse_keys("<C-O><pagedown><lt>"),
+            Ok(vec![Key::Ctrl('o'), Key::PageDown, Key::Char('<')])
+        );
+        assert_eq!(parse_keys("<F5>"), Ok(vec![Key::F(5)]));
+        assert_eq!(parse_keys("<>"), Ok(vec![Key::Char('<'), Key::Char('>')]));
+        assert_eq!(
+            parse_keys("<Foo>"),
+            Err(String::from("unknown key `<Foo>`"))
+        );
+    }
+
+    #[test]
+    fn test_key_sequences() {
+        let mut keymap = Keymap::default();
+        assert_eq!(
+            keymap.press(Key::PageDown).map(|command| command.action),
+            Some(Action::Next)
+        );
+        assert_eq!(press_all(&mut keymap, "gg"), vec![Action::FirstSlide]);
+        // An unbound key drops the started sequence.
+        assert_eq!(press_all(&mut keymap, "gxgl"), vec![Action::Next]);
+        assert_eq!(
+            press_all(&mut keymap, "xggG"),
+            vec![Action::FirstSlide, Action::LastSlide]
+        );
+    }
+
+    #[test]
+    fn test_config_replaces_keys_of_an_action() {
+        let mut keymap =
+            Keymap::parse("[keys]\nnext = [\"n\", \"<Tab>\"]\nquit = [\"g\"]\n").unwrap();
+        assert_eq!(keymap.press(Key::Char('l')), None);
+        assert_eq!(
+            keymap.press(Key::Char('\t')).map(|command| command.action),
+            Some(Action::Next)
+        );
+        assert_eq!(
+            press_all(&mut keymap, "nh"),
+            vec![Action::Next, Action::Previous]
+        );
+        // A listed key replaces the default keys of other actions it conflicts
+        // with, like `gg` for `g`.
+        assert_eq!(press_all(&mut keymap, "g"), vec![Action::Quit]);
+
+        let err = Keymap::parse("[keys]\nskip = [\"s\"]\n").unwrap_err();
+        assert!(err.contains("unknown variant `skip`"), "{}", err);
+    }
+
+    #[test]
+    fn test_config_rejects_conflicting_keys() {
+        let error = |content: &str| Keymap::parse(content).unwrap_err();
+        assert_eq!(
+            error("[keys]\nquit = [\"g\"]\nfirst_slide = [\"gg\"]\n"),
+            "the keys `gg` and `g` conflict, one starts with the other"
+        );
+        // The same in whichever order the actions are listed.
+        assert_eq!(
+            error("[keys]\nfirst_slide = [\"gg\"]\nquit = [\"g\"]\n"),
+            "the keys `gg` and `g` conflict, one starts with the other"
+        );
+        assert_eq!(
+            error("[keys]\nnext = [\"n\"]\nsearch_next = [\"n\"]\n"),
+            "the keys `n` are bound twice"
+        );
+    }
+
+    #[test]
+    fn test_counts() {
+        let mut keymap = Keymap::default();
+        let commands: Vec<Command> = "12G0l5x3gg"
+            .chars()
+            .filter_map(|c| keymap.press(Key::Char(c)))
+            .collect();
+        let command = |action, count| Command { action, count };
+        assert_eq!(
+            commands,
+            vec![
+                command(Action::LastSlide, Some(12)),
+                command(Action::Next, None),
+                command(Action::FirstSlide, Some(3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_prompt() {
+        let mut prompt = Prompt::new(':');
+        assert_eq!(prompt.press(Key::Char('1')), PromptInput::Editing);
+        assert_eq!(prompt.press(Key::Char('3')), PromptInput::Editing);
+        assert_eq!(prompt.press(Key::Backspace), PromptInput::Editing);
+        assert_eq!(
+            prompt.press(Key::Char('\n')),
+            PromptInput::Submitted(String::from("1"))
+        );
+        assert_eq!(
+            Prompt::new(':').press(Key::Backspace),
+            PromptInput::Cancelled
+        );
+    }
+}
@@ -0,0 +1,294 @@
+/// A rectangle of terminal cells, positioned 1-based like `cursor::Goto`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Area {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Area {
+    /// The area between the title and the footer of a slide, leaving `margin`
+    /// columns free on both sides.
+    pub fn slide_content(terminal_width: u16, terminal_height: u16, margin: u16) -> Area {
+        let margin = margin.min(terminal_width.saturating_sub(1) / 2);
+        Area {
+            x: margin + 1,
+            y: 4,
+            width: terminal_width - 2 * margin,
+            height: terminal_height.saturating_sub(5),
+        }
+    }
+}
+
+/// The smallest terminal slides are shown in, leaving five rows for the
+/// content between the title and the footer.
+pub const MIN_WIDTH: u16 = 20;
+pub const MIN_HEIGHT: u16 = 10;
+
+/// Whether a terminal of `width` × `height` cells is too small to show
+/// slides in.
+pub fn is_too_small(width: u16, height: u16) -> bool {
+    width < MIN_WIDTH || height < MIN_HEIGHT
+}
+
+/// The number of rows shown and cut off of content `rows` rows high in an
+/// area `available` rows high. Content that doesn't fit leaves the last row
+/// free to say how much is cut off.
+pub fn cut_off(rows: usize, available: usize) -> (usize, usize) {
+    if rows <= available {
+        return (rows, 0);
+    }
+    let shown = available.saturating_sub(1);
+    (shown, rows - shown)
+}
+
+/// Shrinks columns with the natural `widths` to fit into `available` columns.
+/// Narrow columns keep their width while the space left is shared equally by
+/// the wider ones. Every column keeps at least one column.
+pub fn fit_columns(widths: &[usize], available: usize) -> Vec<usize> {
+    if widths.iter().sum::<usize>() <= available {
+        return widths.to_vec();
+    }
+    let mut fitted = vec![0; widths.len()];
+    let mut remaining: Vec<usize> = (0..widths.len()).collect();
+    let mut space = available;
+    // Columns narrower than an equal share of the space left get their width,
+    // until only columns wider than their share remain.
+    loop {
+        let share = space / remaining.len().max(1);
+        let (narrow, wide): (Vec<usize>, Vec<usize>) =
+            remaining.iter().partition(|&&i| widths[i] <= share);
+        if narrow.is_empty() {
+            break;
+        }
+        for i in narrow {
+            fitted[i] = widths[i];
+            space -= widths[i];
+        }
+        remaining = wide;
+    }
+    let count = remaining.len().max(1);
+    for (n, i) in remaining.into_iter().enumerate() {
+        // The first columns take the columns left over by the division.
+        fitted[i] = (space / count + usize::from(n < space % count)).max(1);
+    }
+    fitted
+}
+
+/// Splits `width` into columns proportional to `ratios`, leaving `gap`
+/// columns between them. Columns left over by rounding go to the last one,
+/// and every column is at least one column wide.
+pub fn split_columns(width: usize, ratios: &[u16], gap: usize) -> Vec<usize> {
+    let available = width.saturating_sub(gap * ratios.len().saturating_sub(1));
+    let total: usize = ratios.iter().map(|ratio| *ratio as usize).sum();
+    let mut widths: Vec<usize> = ratios
+        .iter()
+        .map(|ratio| available * *ratio as usize / total.max(1))
+        .collect();
+    let leftover = available - widths.iter().sum::<usize>();
+    if let Some(last) = widths.last_mut() {
+        *last += leftover;
+    }
+    // Columns widened to one column are paid for by the widest column.
+    let mut widths: Vec<usize> = widths.into_iter().map(|width| width.max(1)).collect();
+    let excess = widths.iter().sum::<usize>().saturating_sub(available);
+    if let Some(widest) = widths.iter_mut().max() {
+        *widest = widest.saturating_sub(excess).max(1);
+    }
+    widths
+}
+
+/// How lines are placed horizontally within an area.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Alignment {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+impl Alignment {
+    pub fn parse(value: &str) -> Option<Alignment> {
+        match value {
+            "left" => Some(Alignment::Left),
+            "center" | "centered" => Some(Alignment::Center),
+            "right" => Some(Alignment::Right),
+            _ => None,
+        }
+    }
+
+    /// The number of columns to skip before content `width` columns wide in
+    /// an area `available` columns wide.
+    pub fn offset(self, width: usize, available: usize) -> usize {
+        let free = available.saturating_sub(width);
+        match self {
+            Alignment::Left => 0,
+            Alignment::Center => free / 2,
+            Alignment::Right => free,
+        }
+    }
+}
+
+/// How content is placed vertically within an area.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum VerticalAlignment {
+    #[default]
+    Top,
+    Center,
+}
+
+impl VerticalAlignment {
+    pub fn parse(value: &str) -> Option<VerticalAlignment> {
+        match value {
+            "top" => Some(VerticalAlignment::Top),
+            "center" | "centered" => Some(VerticalAlignment::Center),
+            _ => None,
+        }
+    }
+
+    /// The number of rows to skip before content `height` rows high in an
+    /// area `available` rows high.
+    pub fn offset(self, height: usize, available: usize) -> usize {
+        match self {
+            VerticalAlignment::Top => 0,
+            VerticalAlignment::Center => available.saturating_sub(height) / 2,
+        }
+    }
+}
+
+/// Thumbnails of the overview are at least this wide, borders included.
+const THUMBNAIL_MIN_WIDTH: u16 = 24;
+/// The height of overview thumbnails, borders included.
+const THUMBNAIL_HEIGHT: u16 = 8;
+/// The empty columns between thumbnails.
+const THUMBNAIL_GAP: u16 = 1;
+
+/// The grid of slide thumbnails in the overview.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Grid {
+    area: Area,
+    pub columns: usize,
+    /// The number of rows of thumbnails that fit into the area.
+    pub rows: usize,
+    thumbnail_width: u16,
+}
+
+impl Grid {
+    /// Fits as many thumbnails side by side into `area` as there is room for,
+    /// sharing the width equally.
+    pub fn new(area: Area) -> Grid {
+        let columns = ((area.width + THUMBNAIL_GAP) / (THUMBNAIL_MIN_WIDTH + THUMBNAIL_GAP)).max(1);
+        Grid {
+            area,
+            columns: columns as usize,
+            rows: (area.height / THUMBNAIL_HEIGHT).max(1) as usize,
+            thumbnail_width: (area.width + THUMBNAIL_GAP) / columns - THUMBNAIL_GAP,
+        }
+    }
+
+    /// The first row of thumbnails to show so that thumbnail `selected` is
+    /// visible, scrolling a row at a time.
+    pub fn first_row(&self, selected: usize) -> usize {
+        (selected / self.columns).saturating_sub(self.rows - 1)
+    }
+
+    /// The area of thumbnail `index` with `first_row` at the top, if visible.
+    pub fn thumbnail(&self, index: usize, first_row: usize) -> Option<Area> {
+        let row = (index / self.columns).checked_sub(first_row)?;
+        if row >= self.rows {
+            return None;
+        }
+        let column = (index % self.columns) as u16;
+        Some(Area {
+            x: self.area.x + column * (self.thumbnail_width + THUMBNAIL_GAP),
+            y: self.area.y + row as u16 * THUMBNAIL_HEIGHT,
+            width: self.thumbnail_width,
+            height: THUMBNAIL_HEIGHT.min(self.area.height),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slide_content_area() {
+        assert_eq!(
+            Area::slide_content(80, 24, 4),
+            Area {
+                x: 5,
+                y: 4,
+                width: 72,
+                height: 19
+            }
+        );
+    }
+
+    #[test]
+    fn test_slide_content_area_shrinks_margin_on_narrow_terminals() {
+        let area = Area::slide_content(5, 3, 4);
+        assert_eq!((area.x, area.width, area.height), (3, 1, 0));
+    }
+
+    #[test]
+    fn test_fit_on_screen() {
+        assert!(is_too_small(19, 24));
+        assert!(is_too_small(80, 9));
+        assert!(!is_too_small(20, 10));
+        assert_eq!(cut_off(5, 19), (5, 0));
+        assert_eq!(cut_off(19, 19), (19, 0));
+        assert_eq!(cut_off(30, 19), (18, 12));
+        assert_eq!(cut_off(3, 0), (0, 3));
+    }
+
+    #[test]
+    fn test_fit_columns() {
+        assert_eq!(fit_columns(&[5, 10], 20), vec![5, 10]);
+        assert_eq!(fit_columns(&[4, 30, 20], 30), vec![4, 13, 13]);
+        assert_eq!(fit_columns(&[10, 10, 10], 3), vec![1, 1, 1]);
+        assert_eq!(fit_columns(&[10, 10], 0), vec![1, 1]);
+    }
+
+    #[test]
+    fn test_split_columns() {
+        assert_eq!(split_columns(80, &[2, 1], 2), vec![52, 26]);
+        assert_eq!(split_columns(11, &[1, 1, 1], 1), vec![3, 3, 3]);
+        assert_eq!(split_columns(12, &[1, 1, 1], 1), vec![3, 3, 4]);
+        assert_eq!(split_columns(76, &[1, 100], 2), vec![1, 73]);
+    }
+
+    #[test]
+    fn test_alignment_offsets() {
+        assert_eq!(Alignment::Center.offset(4, 11), 3);
+        assert_eq!(Alignment::Right.offset(4, 11), 7);
+        assert_eq!(Alignment::Right.offset(12, 11), 0);
+        assert_eq!(VerticalAlignment::Center.offset(3, 10), 3);
+        assert_eq!(VerticalAlignment::Top.offset(3, 10), 0);
+    }
+
+    #[test]
+    fn test_overview_grid() {
+        let grid = Grid::new(Area {
+            x: 3,
+            y: 3,
+            width: 76,
+            height: 20,
+        });
+        assert_eq!((grid.columns, grid.rows), (3, 2));
+        assert_eq!(grid.first_row(4), 0);
+        assert_eq!(grid.first_row(6), 1);
+        assert_eq!(
+            grid.thumbnail(5, 1),
+            Some(Area {
+                x: 53,
+                y: 3,
+                width: 24,
+                height: 8
+            })
+        );
+        assert_eq!(grid.thumbnail(1, 1), None);
+        assert_eq!(grid.thumbnail(9, 1), None);
+    }
+}
@@ -1,44 +1,212 @@
 use std::{
+    collections::{HashMap, HashSet},
     fs,
-    io::{stdin, stdout},
-    path::Path,
+    io::{stdout, Stdout},
+    path::{Path, PathBuf},
     process,
+    time::{Duration, Instant},
 };
 
 use colors::Theme;
-use regex::Regex;
-use termion::{input::TermRead, raw::IntoRawMode};
+use events::Event;
+use figlet::Font;
+use front_matter::{Entry, Value};
+use image::Image;
+use keymap::{Action, Command, Keymap, Prompt, PromptInput};
+use layout::{Alignment, VerticalAlignment};
+use markdown::{Slide, Transition};
+use presenter::PresenterLink;
+use termion::{
+    event::Key,
+    raw::{IntoRawMode, RawTerminal},
+};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
 
 pub mod colors;
+pub mod config;
+pub mod events;
+pub mod figlet;
+pub mod front_matter;
+pub mod graphics;
+pub mod highlighting;
+pub mod image;
+pub mod keymap;
+pub mod layout;
+pub mod markdown;
+pub mod presenter;
 pub mod rendering;
+pub mod terminal;
+pub mod text;
+
+const DEFAULT_MARGIN: u16 = 2;
+const DEFAULT_FONT: &str = "compact";
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Metadata {
-    author: Option<String>,
+    authors: Vec<String>,
     title: Option<String>,
     subtitle: Option<String>,
+    date: Option<String>,
+    event: Option<String>,
+    location: Option<String>,
+    /// `none` disables the generated title slide.
+    title_slide: Option<TitleSlideLayout>,
+    /// Empty columns left and right of the slide content.
+    margin: Option<u16>,
+    /// A TOML theme, relative to the presentation file.
+    theme_file: Option<String>,
+    /// The FIGlet font of the title and level 1 headings.
+    font: Option<String>,
+    /// The placement of slide content, unless a slide sets its own.
+    alignment: Option<Alignment>,
+    vertical_alignment: Option<VerticalAlignment>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TitleSlideLayout {
+    Centered,
+    Left,
+    None,
+}
+
+impl TitleSlideLayout {
+    fn parse(value: &str) -> Option<TitleSlideLayout> {
+        match value {
+            "centered" | "center" | "true" => Some(TitleSlideLayout::Centered),
+            "left" => Some(TitleSlideLayout::Left),
+            "none" | "false" => Some(TitleSlideLayout::None),
+            _ => None,
+        }
+    }
 }
 
-pub struct Presentation<'a> {
+impl Metadata {
+    /// Builds the metadata from the front matter. Keys other than the known
+    /// ones are ignored.
+    fn from_front_matter(entries: Vec<Entry>) -> Result<Metadata, front_matter::Error> {
+        let mut metadata = Metadata::default();
+        for entry in entries {
+            let line = entry.line;
+            let string = |value: Value| match value {
+                Value::String(value) => Ok(String::from(value.trim_end())),
+                _ => Err(front_matter::Error::new(
+                    line,
+                    format!("`{}` must be a single value", entry.key),
+                )),
+            };
+            match entry.key.as_str() {
+                "author" | "authors" => {
+                    metadata.authors = match entry.value {
+                        Value::List(authors) => {
+                            authors.into_iter().map(string).collect::<Result<_, _>>()?
+                        }
+                        value => vec![string(value)?],
+                    }
+                }
+                "title" => metadata.title = Some(string(entry.value)?),
+                "subtitle" => metadata.subtitle = Some(string(entry.value)?),
+                "date" => metadata.date = Some(string(entry.value)?),
+                "event" => metadata.event = Some(string(entry.value)?),
+                "location" => metadata.location = Some(string(entry.value)?),
+                "title_slide" => {
+                    let value = string(entry.value)?;
+                    metadata.title_slide =
+                        Some(TitleSlideLayout::parse(&value).ok_or_else(|| {
+                            front_matter::Error::new(
+                                line,
+                                format!(
+                                    "invalid title_slide `{}`, expected centered, left or none",
+                                    value
+                                ),
+                            )
+                        })?);
+                }
+                "margin" => {
+                    let value = string(entry.value)?;
+                    metadata.margin = Some(value.parse().map_err(|_| {
+                        front_matter::Error::new(
+                            line,
+                            format!("invalid margin `{}`, expected a number of columns", value),
+                        )
+                    })?);
+                }
+                "theme_file" => metadata.theme_file = Some(string(entry.value)?),
+                "font" => metadata.font = Some(string(entry.value)?),
+                "alignment" => {
+                    let value = string(entry.value)?;
+                    metadata.alignment = Some(Alignment::parse(&value).ok_or_else(|| {
+                        front_matter::Error::new(
+                            line,
+                            format!(
+                                "invalid alignment `{}`, expected left, center or right",
+                                value
+                            ),
+                        )
+                    })?);
+                }
+                "vertical_alignment" => {
+                    let value = string(entry.value)?;
+                    metadata.vertical_alignment =
+                        Some(VerticalAlignment::parse(&value).ok_or_else(|| {
+                            front_matter::Error::new(
+                                line,
+                                format!(
+                                    "invalid vertical_alignment `{}`, expected top or center",
+                                    value
+                                ),
+                            )
+                        })?);
+                }
+                _ => {}
+            }
+        }
+        Ok(metadata)
+    }
+
+    pub fn title_slide_layout(&self) -> TitleSlideLayout {
+        match (&self.title, self.title_slide) {
+            (None, _) => TitleSlideLayout::None,
+            (Some(_), layout) => layout.unwrap_or(TitleSlideLayout::Centered),
+        }
+    }
+}
+
+pub struct Presentation {
     current_slide: usize,
-    slides: Vec<&'a str>,
+    current_step: usize,
+    slides: Vec<Slide>,
     metadata: Metadata,
     current_theme_index: usize,
-    themes: Vec<&'a Theme>,
+    themes: Vec<Theme>,
+    font: Option<Font>,
+    /// The decoded local images of the slides by their URL.
+    images: HashMap<String, Image>,
+    /// The images as last rendered for the terminal.
+    graphics: graphics::Cache,
+    /// The text searched for last, highlighted on the slides.
+    search: Option<String>,
+    /// The slides and steps left by jumps, oldest first.
+    jumps: Vec<(usize, usize)>,
+    /// The index in `jumps` of the position walked back to, or the length of
+    /// `jumps` when not walking the jump list.
+    jump_index: usize,
 }
 
-impl Presentation<'_> {
-    pub fn new(metadata: Metadata, slides: Vec<&str>) -> Presentation {
+impl Presentation {
+    pub fn new(metadata: Metadata, slides: Vec<Slide>, themes: Vec<Theme>) -> Presentation {
         Presentation {
             current_slide: 0,
+            current_step: 0,
             slides,
             metadata,
             current_theme_index: 0,
-            themes: vec![
-                &Theme::CatppuccinLatte,
-                &Theme::CatppuccinMocha,
-                &Theme::OneDark,
-            ],
+            themes,
+            font: None,
+            images: HashMap::new(),
+            graphics: graphics::Cache::default(),
+            search: None,
+            jumps: Vec::new(),
+            jump_index: 0,
         }
     }
 
@@ -46,11 +214,146 @@ impl Presentation<'_> {
         self.slides.len()
     }
 
-    pub fn current_slide(&self) -> &str {
-        self.slides[self.current_slide]
+    pub fn current_slide(&self) -> &Slide {
+        &self.slides[self.current_slide]
+    }
+
+    pub fn margin(&self) -> u16 {
+        self.metadata.margin.unwrap_or(DEFAULT_MARGIN)
+    }
+
+    /// Moves to `step` of `slide`, clamping both to the existing ones.
+    pub fn set_position(&mut self, slide: usize, step: usize) {
+        self.current_slide = slide.min(self.slides.len() - 1);
+        self.current_step = step.min(self.current_slide().steps() - 1);
+    }
+
+    /// Moves to the first step of slide `index`, counted from 0.
+    pub fn go_to(&mut self, index: usize) -> Result<(), String> {
+        if index >= self.slides.len() {
+            return Err(format!(
+                "There is no slide {}, the presentation has {}",
+                index + 1,
+                self.slides.len()
+            ));
+        }
+        self.record_jump();
+        self.current_slide = index;
+        self.current_step = 0;
+        Ok(())
+    }
+
+    /// Adds the current position to the jump list before a jump. Positions
+    /// walked back past are dropped.
+    fn record_jump(&mut self) {
+        let position = (self.current_slide, self.current_step);
+        self.jumps.truncate(self.jump_index);
+        if self.jumps.last() != Some(&position) {
+            self.jumps.push(position);
+        }
+        self.jump_index = self.jumps.len();
     }
+
+    /// Goes back to the position before the last jump, if any.
+    pub fn jump_back(&mut self) {
+        if self.jump_index == 0 {
+            return;
+        }
+        // Remember where the walk started to be able to come back.
+        if self.jump_index == self.jumps.len() {
+            let position = (self.current_slide, self.current_step);
+            if self.jumps.last() != Some(&position) {
+                self.jumps.push(position);
+            }
+        }
+        self.jump_index -= 1;
+        let (slide, step) = self.jumps[self.jump_index];
+        self.set_position(slide, step);
+    }
+
+    /// Goes forward again to where `jump_back` came from.
+    pub fn jump_forward(&mut self) {
+        if self.jump_index + 1 >= self.jumps.len() {
+            return;
+        }
+        self.jump_index += 1;
+        let (slide, step) = self.jumps[self.jump_index];
+        self.set_position(slide, step);
+    }
+
+    /// Moves to the next slide containing `query`, ignoring case, or the
+    /// previous one if `backwards`. The search wraps around and reaches the
+    /// current slide last. The slide is shown with all its steps revealed.
+    pub fn search(&mut self, query: &str, backwards: bool) -> Result<(), String> {
+        let count = self.slides.len();
+        let lowercase = query.to_lowercase();
+        let found = (1..=count)
+            .map(|distance| match backwards {
+                true => (self.current_slide + count - distance) % count,
+                false => (self.current_slide + distance) % count,
+            })
+            .find(|index| self.slide_text(*index).to_lowercase().contains(&lowercase))
+            .ok_or_else(|| format!("`{}` not found", query))?;
+        self.record_jump();
+        self.set_position(found, usize::MAX);
+        Ok(())
+    }
+
+    /// The text of slide `index`, from the metadata for the title slide.
+    fn slide_text(&self, index: usize) -> String {
+        let slide = &self.slides[index];
+        if !slide.is_title_slide {
+            return slide.plain_text();
+        }
+        let metadata = &self.metadata;
+        [&metadata.title, &metadata.subtitle]
+            .into_iter()
+            .flatten()
+            .chain(&metadata.authors)
+            .map(|text| format!("{}\n", text))
+            .collect()
+    }
+
+    /// Replaces the content while staying on the current slide as far as it
+    /// still exists. The theme is kept, the rendered images are forgotten.
+    pub fn reload(&mut self, metadata: Metadata, slides: Vec<Slide>) {
+        self.metadata = metadata;
+        self.slides = slides;
+        self.graphics.clear();
+        self.set_position(self.current_slide, self.current_step);
+    }
+
+    /// The next slide, if any.
+    pub fn next_slide(&self) -> Option<&Slide> {
+        self.slides.get(self.current_slide + 1)
+    }
+
+    /// The number of reveal steps of all slides.
+    pub fn total_steps(&self) -> usize {
+        self.slides.iter().map(Slide::steps).sum()
+    }
+
+    /// The index of the current reveal step within the whole presentation.
+    pub fn current_step_position(&self) -> usize {
+        self.slides[..self.current_slide]
+            .iter()
+            .map(Slide::steps)
+            .sum::<usize>()
+            + self.current_step
+    }
+
     pub fn current_theme(&self) -> &Theme {
-        self.themes[self.current_theme_index]
+        &self.themes[self.current_theme_index]
+    }
+
+    /// The theme the current slide is shown in, its own if it names one.
+    pub fn slide_theme(&self) -> &Theme {
+        self.current_slide()
+            .options
+            .theme
+            .as_ref()
+            .and_then(|name| self.themes.iter().find(|theme| theme.get_name() == name))
+            .unwrap_or(self.current_theme())
     }
 
     pub fn cycle_theme(&mut self) {
@@ -58,60 +361,100 @@ impl Presentation<'_> {
     }
 
     pub fn move_to_previous_slide(&mut self) {
-        self.current_slide = self.current_slide.saturating_sub(1);
+        if self.current_step > 0 {
+            self.current_step -= 1;
+        } else if self.current_slide > 0 {
+            self.current_slide -= 1;
+            self.current_step = self.current_slide().steps() - 1;
+        }
     }
 
     pub fn move_to_next_slide(&mut self) {
-        if self.current_slide < self.slides.len() - 1 {
+        if self.current_step + 1 < self.current_slide().steps() {
+            self.current_step += 1;
+        } else if self.current_slide < self.slides.len() - 1 {
             self.current_slide = self.current_slide.saturating_add(1);
+            self.current_step = 0;
         }
     }
 }
 
 #[tokio::main]
 async fn main() {
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() > 1 {
-        let presentation_file = &args[1];
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let presenter_mode = args.iter().any(|arg| arg == "--presenter");
+    if let Some(presentation_file) = args.iter().find(|arg| !arg.starts_with("--")) {
         if !Path::new(presentation_file).exists() {
             eprintln!("The file {} does not exist!", presentation_file);
             process::exit(1);
         }
         match fs::read_to_string(presentation_file) {
             Ok(content) => {
-                let (metadata, content_without_metadata) = parse_metadata(&content);
-                let slides: Vec<&str> = content_without_metadata
-                    .split("<!-- end_slide -->")
-                    .collect();
-                let mut presentation = Presentation::new(metadata, slides);
-                let stdin = stdin();
-                let mut stdout = stdout().into_raw_mode().unwrap();
-                rendering::render_slide(&presentation, &mut stdout);
-                for c in stdin.keys() {
-                    match c.unwrap() {
-                        termion::event::Key::Char('h') => {
-                            presentation.move_to_previous_slide();
-                        }
-                        termion::event::Key::Char('l') => {
-                            presentation.move_to_next_slide();
-                        }
-                        termion::event::Key::Char('t') => {
-                            presentation.cycle_theme();
-                            rendering::render_slide(&presentation, &mut stdout);
-                            rendering::render_notification(
-                                presentation.current_theme().get_name(),
-                                &mut stdout,
-                                presentation.current_theme().get_theme_colors().text,
-                            )
-                            .await;
-                        }
-                        termion::event::Key::Char('q') => {
-                            break;
-                        }
-                        _ => {}
-                    }
-                    rendering::render_slide(&presentation, &mut stdout);
+                let (metadata, slides) = parse_presentation(&content).unwrap_or_else(|err| {
+                    eprintln!(
+                        "Error in the front matter of {}, {}",
+                        presentation_file, err
+                    );
+                    process::exit(1);
+                });
+                let (themes, initial_theme) = load_themes(Path::new(presentation_file), &metadata)
+                    .unwrap_or_else(|err| {
+                        eprintln!("{}", err);
+                        process::exit(1);
+                    });
+                if let Err(err) = check_slide_themes(&slides, &themes) {
+                    eprintln!("Error in {}, {}", presentation_file, err);
+                    process::exit(1);
                 }
+                let font =
+                    load_font(Path::new(presentation_file), &metadata).unwrap_or_else(|err| {
+                        eprintln!("{}", err);
+                        process::exit(1);
+                    });
+                let (images, image_error) = load_images(Path::new(presentation_file), &slides);
+                let keymap = load_keymap().unwrap_or_else(|err| {
+                    eprintln!("{}", err);
+                    process::exit(1);
+                });
+                let mut presentation = Presentation::new(metadata, slides, themes);
+                presentation.current_theme_index = initial_theme;
+                presentation.font = font;
+                presentation.images = images;
+                let (sender, events) = mpsc::unbounded_channel();
+                let socket = presenter::socket_path(Path::new(presentation_file));
+                let link = if presenter_mode {
+                    events::spawn_ticker(sender.clone(), Duration::from_secs(1));
+                    PresenterLink::connect(&socket, sender.clone()).await
+                } else {
+                    PresenterLink::listen(&socket, sender.clone())
+                };
+                let link = link.unwrap_or_else(|err| {
+                    match presenter_mode {
+                        true => eprintln!(
+                            "No running presentation of {} found: {}",
+                            presentation_file, err
+                        ),
+                        false => eprintln!("Error opening the presenter socket: {}", err),
+                    }
+                    process::exit(1);
+                });
+                events::spawn_file_watcher(
+                    PathBuf::from(presentation_file),
+                    sender.clone(),
+                    Duration::from_millis(500),
+                );
+                events::spawn_resize_listener(sender.clone());
+                events::spawn_key_reader(sender);
+                run(
+                    presentation,
+                    presentation_file,
+                    presenter_mode,
+                    image_error,
+                    keymap,
+                    link,
+                    events,
+                )
+                .await;
             }
             Err(err) => {
                 eprintln!("Error reading file: {}", err);
@@ -120,30 +463,665 @@ async fn main() {
         }
     } else {
         eprintln!("Please provide a presentation markdown file as an argument!");
+        eprintln!("Pass --presenter to open the presenter view of a running presentation.");
         process::exit(1);
     }
 }
 
-fn parse_metadata(content: &str) -> (Metadata, String) {
-    let re = Regex::new(r"(author|title|subtitle): (.*?)\n").unwrap();
-    let mut metadata = Metadata {
-        author: None,
-        title: None,
-        subtitle: None,
+async fn run(
+    mut presentation: Presentation,
+    presentation_file: &str,
+    presenter_mode: bool,
+    // Shown once the first slide is on the screen.
+    mut notification: Option<String>,
+    mut keymap: Keymap,
+    link: PresenterLink,
+    mut events: UnboundedReceiver<Event>,
+) {
+    let started = Instant::now();
+    let mut slide_started = started;
+    let mut stdout = stdout().into_raw_mode().unwrap();
+    let render =
+        |presentation: &Presentation, slide_started: Instant, stdout: &mut RawTerminal<Stdout>| {
+            if presenter_mode {
+                rendering::render_presenter_view(
+                    presentation,
+                    started.elapsed(),
+                    slide_started.elapsed(),
+                    stdout,
+                );
+            } else {
+                rendering::render_slide(presentation, stdout);
+            }
+        };
+    // The `:` or `/` prompt while a slide number or search is typed.
+    let mut prompt: Option<Prompt> = None;
+    // The slide selected in the overview while it is shown.
+    let mut overview: Option<usize> = None;
+    render(&presentation, slide_started, &mut stdout);
+    if let Some(text) = notification.take() {
+        notify(&presentation, &text, &mut stdout).await;
+    }
+    // An event that cut a transition short, handled before the next one.
+    let mut interrupting: Option<Event> = None;
+    loop {
+        let event = match interrupting.take() {
+            Some(event) => event,
+            None => match events.recv().await {
+                Some(event) => event,
+                None => break,
+            },
+        };
+        let position = (presentation.current_slide, presentation.current_step);
+        match event {
+            Event::Key(key) if overview.is_some() => {
+                let selected = overview.unwrap();
+                match key {
+                    Key::Char('\n') => {
+                        overview = None;
+                        presentation.go_to(selected).unwrap();
+                    }
+                    Key::Esc | Key::Char('q') | Key::Char('o') => overview = None,
+                    key => {
+                        let columns = rendering::overview_grid().columns;
+                        let total = presentation.total_slides();
+                        match move_in_overview(key, selected, columns, total) {
+                            Some(moved) => overview = Some(moved),
+                            None => continue,
+                        }
+                    }
+                }
+            }
+            Event::Key(key) => {
+                let moved = match &mut prompt {
+                    Some(open) => match open.press(key) {
+                        PromptInput::Editing => {
+                            let colors = presentation.slide_theme().get_theme_colors();
+                            rendering::render_prompt(
+                                open,
+                                &mut stdout,
+                                colors.text,
+                                colors.background,
+                            );
+                            continue;
+                        }
+                        PromptInput::Cancelled => {
+                            prompt = None;
+                            Ok(())
+                        }
+                        PromptInput::Submitted(text) => {
+                            let symbol = open.symbol;
+                            prompt = None;
+                            match symbol {
+                                '/' => search(&mut presentation, text),
+                                _ => match text.trim().parse() {
+                                    Ok(number) => go_to_slide_number(&mut presentation, number),
+                                    Err(_) => Err(format!("`{}` is not a slide number", text)),
+                                },
+                            }
+                        }
+                    },
+                    None => match keymap.press(key) {
+                        Some(
+                            command @ (Command {
+                                action: Action::GoToSlide,
+                                count: None,
+                            }
+                            | Command {
+                                action: Action::Search,
+                                ..
+                            }),
+                        ) => {
+                            let symbol = match command.action {
+                                Action::Search => '/',
+                                _ => ':',
+                            };
+                            let open = prompt.insert(Prompt::new(symbol));
+                            let colors = presentation.slide_theme().get_theme_colors();
+                            rendering::render_prompt(
+                                open,
+                                &mut stdout,
+                                colors.text,
+                                colors.background,
+                            );
+                            continue;
+                        }
+                        Some(Command {
+                            action: Action::CycleTheme,
+                            ..
+                        }) => {
+                            presentation.cycle_theme();
+                            render(&presentation, slide_started, &mut stdout);
+                            let name = presentation.current_theme().get_name();
+                            notify(&presentation, name, &mut stdout).await;
+                            continue;
+                        }
+                        Some(Command {
+                            action: Action::Overview,
+                            ..
+                        }) => {
+                            overview = Some(presentation.current_slide);
+                            Ok(())
+                        }
+                        Some(Command {
+                            action: Action::Quit,
+                            ..
+                        }) => break,
+                        Some(command) => run_command(&mut presentation, command),
+                        None => continue,
+                    },
+                };
+                if let Err(err) = moved {
+                    render(&presentation, slide_started, &mut stdout);
+                    notify(&presentation, &err, &mut stdout).await;
+                    continue;
+                }
+            }
+            Event::Goto { slide, step, peer } => {
+                presentation.set_position(slide, step);
+                // Pass moves of one presenter view on to the others.
+                if !presenter_mode && position != (slide, step) {
+                    link.send_position(slide, step, Some(peer)).await;
+                }
+            }
+            Event::PeerConnected => {
+                link.send_position(position.0, position.1, None).await;
+                continue;
+            }
+            Event::FileChanged => {
+                // The file may be read mid-write, the next change fixes that.
+                let Ok(content) = fs::read_to_string(presentation_file) else {
+                    continue;
+                };
+                let reloaded = parse_presentation(&content)
+                    .and_then(|(metadata, slides)| {
+                        check_slide_themes(&slides, &presentation.themes)?;
+                        Ok((metadata, slides))
+                    })
+                    .map_err(|err| format!("Error in {}", err))
+                    .and_then(|(metadata, slides)| {
+                        let font = load_font(Path::new(presentation_file), &metadata)?;
+                        Ok((metadata, slides, font))
+                    });
+                match reloaded {
+                    Ok((metadata, slides, font)) => {
+                        let (images, image_error) =
+                            load_images(Path::new(presentation_file), &slides);
+                        notification = image_error;
+                        presentation.reload(metadata, slides);
+                        let last = presentation.total_slides() - 1;
+                        overview = overview.map(|selected| selected.min(last));
+                        presentation.font = font;
+                        presentation.images = images;
+                    }
+                    // Keep showing the last good version until the error is fixed.
+                    Err(err) => {
+                        render(&presentation, slide_started, &mut stdout);
+                        notify(&presentation, &err, &mut stdout).await;
+                        continue;
+                    }
+                }
+            }
+            // The size of the cells may have changed with the terminal's.
+            Event::Resize => presentation.graphics.clear(),
+            Event::Tick if overview.is_some() => continue,
+            Event::Tick => {
+                rendering::render_presenter_clock(
+                    &presentation,
+                    started.elapsed(),
+                    slide_started.elapsed(),
+                    &mut stdout,
+                );
+                continue;
+            }
+        }
+        let new_position = (presentation.current_slide, presentation.current_step);
+        if matches!(event, Event::Key(_)) && new_position != position {
+            link.send_position(new_position.0, new_position.1, None)
+                .await;
+        }
+        if new_position.0 != position.0 {
+            slide_started = Instant::now();
+            if !presenter_mode && overview.is_none() {
+                if let Some(transition) = presentation.current_slide().options.transition {
+                    interrupting =
+                        animate_transition(&presentation, transition, &mut events, &mut stdout)
+                            .await;
+                }
+            }
+        }
+        match overview {
+            Some(selected) => rendering::render_overview(&presentation, selected, &mut stdout),
+            None => render(&presentation, slide_started, &mut stdout),
+        }
+        if let Some(open) = &prompt {
+            let colors = presentation.slide_theme().get_theme_colors();
+            rendering::render_prompt(open, &mut stdout, colors.text, colors.background);
+        }
+        if let Some(text) = notification.take() {
+            notify(&presentation, &text, &mut stdout).await;
+        }
+    }
+}
+
+/// Animates the content of the current slide coming in, stopping at the next
+/// event, which is returned to be handled. The caller renders the slide
+/// afterwards.
+async fn animate_transition(
+    presentation: &Presentation,
+    transition: Transition,
+    events: &mut UnboundedReceiver<Event>,
+    stdout: &mut RawTerminal<Stdout>,
+) -> Option<Event> {
+    for frame in 1..rendering::TRANSITION_FRAMES {
+        rendering::render_transition_frame(presentation, transition, frame, stdout);
+        tokio::select! {
+            _ = tokio::time::sleep(rendering::TRANSITION_FRAME_TIME) => {}
+            event = events.recv() => return event,
+        }
+    }
+    None
+}
+
+/// Shows `text` for a few seconds in the colours of the current theme.
+async fn notify(presentation: &Presentation, text: &str, stdout: &mut RawTerminal<Stdout>) {
+    let colors = presentation.current_theme().get_theme_colors();
+    rendering::render_notification(text, stdout, colors.text, colors.background).await;
+}
+
+/// Carries out the actions of `command` that only change the presentation.
+/// A count repeats moving and searching and selects the slide to go to for
+/// the other moves.
+fn run_command(presentation: &mut Presentation, command: Command) -> Result<(), String> {
+    // Going further than there are steps changes nothing.
+    let repeat = command.count.unwrap_or(1).min(presentation.total_steps());
+    match (command.action, command.count) {
+        (Action::Next, _) => (0..repeat).for_each(|_| presentation.move_to_next_slide()),
+        (Action::Previous, _) => (0..repeat).for_each(|_| presentation.move_to_previous_slide()),
+        (Action::SearchNext | Action::SearchPrevious, _) => {
+            let query = presentation
+                .search
+                .clone()
+                .ok_or("Nothing searched for yet")?;
+            for _ in 0..repeat {
+                presentation.search(&query, command.action == Action::SearchPrevious)?;
+            }
+        }
+        (Action::ClearSearch, _) => presentation.search = None,
+        (Action::JumpBack, _) => (0..repeat).for_each(|_| presentation.jump_back()),
+        (Action::JumpForward, _) => (0..repeat).for_each(|_| presentation.jump_forward()),
+        (Action::FirstSlide | Action::LastSlide | Action::GoToSlide, Some(number)) => {
+            return go_to_slide_number(presentation, number)
+        }
+        (Action::FirstSlide, None) => return presentation.go_to(0),
+        (Action::LastSlide, None) => return presentation.go_to(presentation.total_slides() - 1),
+        (
+            Action::GoToSlide
+            | Action::Overview
+            | Action::Search
+            | Action::CycleTheme
+            | Action::Quit,
+            _,
+        ) => {}
+    }
+    Ok(())
+}
+
+/// Searches for `text` typed at the `/` prompt, or for the text searched for
+/// before if it's empty, and highlights it.
+fn search(presentation: &mut Presentation, text: String) -> Result<(), String> {
+    let Some(query) = Some(text)
+        .filter(|text| !text.is_empty())
+        .or(presentation.search.take())
+    else {
+        return Ok(());
+    };
+    presentation.search = Some(query.clone());
+    presentation.search(&query, false)
+}
+
+/// The slide selected in the overview of `total` slides in rows of `columns`
+/// after pressing `key`, if it is an arrow key or one of `hjkl`.
+fn move_in_overview(key: Key, selected: usize, columns: usize, total: usize) -> Option<usize> {
+    let last = total - 1;
+    match key {
+        Key::Left | Key::Char('h') => Some(selected.saturating_sub(1)),
+        Key::Right | Key::Char('l') => Some((selected + 1).min(last)),
+        Key::Up | Key::Char('k') => Some(selected.checked_sub(columns).unwrap_or(selected)),
+        // Into the last row even if it doesn't reach below the selection.
+        Key::Down | Key::Char('j') if selected / columns < last / columns => {
+            Some((selected + columns).min(last))
+        }
+        Key::Down | Key::Char('j') => Some(selected),
+        _ => None,
+    }
+}
+
+/// Goes to the slide with `number` as shown in the footer, counted from 1.
+fn go_to_slide_number(presentation: &mut Presentation, number: usize) -> Result<(), String> {
+    match number {
+        0 => Err(String::from("Slides are numbered from 1")),
+        number => presentation.go_to(number - 1),
+    }
+}
+
+/// Loads the built-in themes, the user's themes and the theme file named in
+/// the metadata. Returns them with the index of the theme to start with.
+fn load_themes(
+    presentation_file: &Path,
+    metadata: &Metadata,
+) -> Result<(Vec<Theme>, usize), String> {
+    let mut themes = Theme::built_in();
+    if let Some(config_dir) = config::config_dir() {
+        colors::load_theme_directory(&config_dir.join("themes"), &mut themes)?;
+    }
+    let mut initial_theme = 0;
+    if let Some(theme_file) = &metadata.theme_file {
+        let directory = presentation_file.parent().unwrap_or(Path::new("."));
+        let theme = Theme::load(&directory.join(theme_file), &themes)?;
+        initial_theme = colors::add_theme(&mut themes, theme);
+    }
+    Ok((themes, initial_theme))
+}
+
+/// Loads the key bindings of the user's config file, if there is one.
+fn load_keymap() -> Result<Keymap, String> {
+    match config::config_dir().map(|dir| dir.join("config.toml")) {
+        Some(path) if path.exists() => Keymap::load(&path),
+        _ => Ok(Keymap::default()),
+    }
+}
+
+/// Loads the font named in the metadata: one of the bundled fonts, a `.flf`
+/// file relative to the presentation file or one in the user's font directory.
+/// `none` turns large headings off.
+fn load_font(presentation_file: &Path, metadata: &Metadata) -> Result<Option<Font>, String> {
+    let name = metadata.font.as_deref().unwrap_or(DEFAULT_FONT);
+    if name == "none" {
+        return Ok(None);
+    }
+    if name.ends_with(".flf") {
+        let directory = presentation_file.parent().unwrap_or(Path::new("."));
+        return Font::load(&directory.join(name)).map(Some);
+    }
+    if let Some(font) = Font::bundled(name) {
+        return Ok(Some(font));
+    }
+    match config::config_dir().map(|dir| dir.join("fonts").join(format!("{}.flf", name))) {
+        Some(path) if path.exists() => Font::load(&path).map(Some),
+        _ => Err(format!(
+            "Unknown font `{}`, use compact, block, none or the path of a .flf file",
+            name
+        )),
+    }
+}
+
+/// Decodes the images of the slides, with paths relative to the presentation
+/// file. Remote images and images that can't be loaded are left out and shown
+/// as their alt text; the error of the first one that can't is returned.
+fn load_images(
+    presentation_file: &Path,
+    slides: &[Slide],
+) -> (HashMap<String, Image>, Option<String>) {
+    let directory = presentation_file.parent().unwrap_or(Path::new("."));
+    let mut images = HashMap::new();
+    let mut loaded = HashSet::new();
+    let mut errors = Vec::new();
+    for url in slides.iter().flat_map(Slide::image_urls) {
+        if url.contains("://") || !loaded.insert(url) {
+            continue;
+        }
+        match Image::load(&directory.join(url)) {
+            Ok(image) => {
+                images.insert(String::from(url), image);
+            }
+            Err(err) => errors.push(err),
+        }
+    }
+    let error = match errors.len() {
+        0 | 1 => errors.pop(),
+        count => Some(format!("{} (and {} more)", errors[0], count - 1)),
     };
+    (images, error)
+}
 
-    for cap in re.captures_iter(content) {
-        let key = &cap[1];
-        let value = cap[2].trim().to_string();
-        match key {
-            "author" => metadata.author = Some(value),
-            "title" => metadata.title = Some(value),
-            "subtitle" => metadata.subtitle = Some(value),
-            _ => {}
+/// Checks that the themes named in slide options exist.
+fn check_slide_themes(slides: &[Slide], themes: &[Theme]) -> Result<(), front_matter::Error> {
+    for slide in slides {
+        if let Some(name) = &slide.options.theme {
+            if !themes.iter().any(|theme| theme.get_name() == name) {
+                return Err(front_matter::Error::new(
+                    slide.options.line,
+                    format!("unknown theme `{}`", name),
+                ));
+            }
         }
     }
-    let content_without_metadata = Regex::new(r"(?s)^---\n.*\n---\n")
-        .unwrap()
-        .replace(content, "");
-    (metadata, content_without_metadata.to_string())
+    Ok(())
+}
+
+fn parse_presentation(content: &str) -> Result<(Metadata, Vec<Slide>), front_matter::Error> {
+    let (front_matter, content_without_metadata) = front_matter::split(content, 1)?;
+    let metadata = Metadata::from_front_matter(front_matter)?;
+    let mut line = 1 + content[..content.len() - content_without_metadata.len()]
+        .matches('\n')
+        .count();
+    let mut slides = Vec::new();
+    for slide in content_without_metadata.split("<!-- end_slide -->") {
+        slides.push(Slide::parse_with_options(slide, line)?);
+        line += slide.matches('\n').count();
+    }
+    if metadata.title_slide_layout() != TitleSlideLayout::None {
+        slides.insert(0, Slide::title_slide());
+    }
+    Ok((metadata, slides))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn presentation(slides: &[&str]) -> Presentation {
+        let metadata = Metadata::default();
+        Presentation::new(
+            metadata,
+            slides.iter().map(|s| Slide::parse(s)).collect(),
+            Theme::built_in(),
+        )
+    }
+
+    #[test]
+    fn test_navigation_steps_through_pauses() {
+        let mut presentation = presentation(&["a\n\n<!-- pause -->\n\nb", "c"]);
+        assert_eq!(presentation.total_steps(), 3);
+        presentation.move_to_next_slide();
+        assert_eq!(
+            (presentation.current_slide, presentation.current_step),
+            (0, 1)
+        );
+        presentation.move_to_next_slide();
+        assert_eq!(
+            (presentation.current_slide, presentation.current_step),
+            (1, 0)
+        );
+        assert_eq!(presentation.current_step_position(), 2);
+        presentation.move_to_previous_slide();
+        assert_eq!(
+            (presentation.current_slide, presentation.current_step),
+            (0, 1)
+        );
+    }
+
+    #[test]
+    fn test_go_to_validates_index() {
+        let mut presentation = presentation(&["a\n\n<!-- pause -->\n\nb", "c"]);
+        presentation.move_to_next_slide();
+        assert_eq!(presentation.go_to(1), Ok(()));
+        assert_eq!(presentation.go_to(0), Ok(()));
+        assert_eq!(
+            (presentation.current_slide, presentation.current_step),
+            (0, 0)
+        );
+        assert_eq!(
+            presentation.go_to(2),
+            Err(String::from("There is no slide 3, the presentation has 2"))
+        );
+    }
+
+    #[test]
+    fn test_commands_with_counts() {
+        let mut presentation = presentation(&["a", "b", "c", "d"]);
+        let command = |action, count| Command { action, count };
+        run_command(&mut presentation, command(Action::Next, Some(2))).unwrap();
+        assert_eq!(presentation.current_slide, 2);
+        run_command(&mut presentation, command(Action::Previous, Some(100))).unwrap();
+        assert_eq!(presentation.current_slide, 0);
+        run_command(&mut presentation, command(Action::FirstSlide, Some(4))).unwrap();
+        assert_eq!(presentation.current_slide, 3);
+        assert!(run_command(&mut presentation, command(Action::LastSlide, Some(0))).is_err());
+        assert_eq!(presentation.current_slide, 3);
+    }
+
+    #[test]
+    fn test_search_wraps_around() {
+        let mut presentation = presentation(&[
+            "# Rust",
+            "```\nlet rust = 1;\n```",
+            "text\n\n<!-- pause -->\n\nmore\n\n<!-- speaker_note: About RUST -->",
+        ]);
+        presentation.search("rust", false).unwrap();
+        assert_eq!(presentation.current_slide, 1);
+        presentation.search("rust", false).unwrap();
+        assert_eq!(
+            (presentation.current_slide, presentation.current_step),
+            (2, 1)
+        );
+        presentation.search("rust", false).unwrap();
+        assert_eq!(presentation.current_slide, 0);
+        presentation.search("Rust", true).unwrap();
+        assert_eq!(presentation.current_slide, 2);
+        assert_eq!(
+            presentation.search("python", false),
+            Err(String::from("`python` not found"))
+        );
+        assert_eq!(presentation.current_slide, 2);
+    }
+
+    #[test]
+    fn test_jump_list() {
+        let mut presentation = presentation(&["a", "b", "c", "d", "e"]);
+        presentation.go_to(3).unwrap();
+        presentation.move_to_next_slide();
+        presentation.go_to(1).unwrap();
+        presentation.jump_back();
+        assert_eq!(presentation.current_slide, 4);
+        presentation.jump_back();
+        assert_eq!(presentation.current_slide, 0);
+        presentation.jump_back();
+        assert_eq!(presentation.current_slide, 0);
+        presentation.jump_forward();
+        presentation.jump_forward();
+        assert_eq!(presentation.current_slide, 1);
+        presentation.jump_forward();
+        assert_eq!(presentation.current_slide, 1);
+
+        // A new jump drops the positions walked back past.
+        presentation.jump_back();
+        presentation.jump_back();
+        presentation.go_to(2).unwrap();
+        assert_eq!(presentation.jumps, vec![(0, 0)]);
+        presentation.jump_back();
+        presentation.jump_forward();
+        assert_eq!(presentation.current_slide, 2);
+    }
+
+    #[test]
+    fn test_move_in_overview() {
+        // Slides 0 to 6 in rows of three.
+        let move_by = |key, selected| move_in_overview(key, selected, 3, 7);
+        assert_eq!(move_by(Key::Right, 6), Some(6));
+        assert_eq!(move_by(Key::Char('h'), 3), Some(2));
+        assert_eq!(move_by(Key::Down, 1), Some(4));
+        assert_eq!(move_by(Key::Down, 5), Some(6));
+        assert_eq!(move_by(Key::Down, 6), Some(6));
+        assert_eq!(move_by(Key::Up, 1), Some(1));
+        assert_eq!(move_by(Key::Char('x'), 1), None);
+    }
+
+    #[test]
+    fn test_reload_clamps_position() {
+        let mut presentation = presentation(&["a", "b", "c"]);
+        presentation.move_to_next_slide();
+        presentation.move_to_next_slide();
+        presentation.cycle_theme();
+        let (metadata, slides) = parse_presentation("a\n<!-- end_slide -->\nb").unwrap();
+        presentation.reload(metadata, slides);
+        assert_eq!(presentation.current_slide, 1);
+        assert_eq!(presentation.current_theme_index, 1);
+    }
+
+    #[test]
+    fn test_title_slide_generated_from_metadata() {
+        let (metadata, slides) = parse_presentation("---\ntitle: Talk\n---\n# First").unwrap();
+        assert_eq!(metadata.title_slide_layout(), TitleSlideLayout::Centered);
+        assert_eq!(slides.len(), 2);
+        assert!(slides[0].is_title_slide);
+
+        let (_, slides) =
+            parse_presentation("---\ntitle: Talk\ntitle_slide: none\n---\n# First").unwrap();
+        assert_eq!(slides.len(), 1);
+        assert!(!slides[0].is_title_slide);
+    }
+
+    #[test]
+    fn test_metadata_only_from_the_front_matter() {
+        let (metadata, slides) = parse_presentation(
+            "---\ntitle: \"Talk: part 2\"\nauthor:\n  - A\n  - B\nvenue: ignored\nalignment: center\n---\ntitle: not metadata\n",
+        )
+        .unwrap();
+        assert_eq!(metadata.title.as_deref(), Some("Talk: part 2"));
+        assert_eq!(metadata.authors, vec!["A", "B"]);
+        assert_eq!(metadata.alignment, Some(Alignment::Center));
+        assert_eq!(slides.len(), 2);
+    }
+
+    #[test]
+    fn test_invalid_metadata_reports_line() {
+        let err = parse_presentation("---\ntitle: Talk\nmargin: wide\n---\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 3: invalid margin `wide`, expected a number of columns"
+        );
+    }
+
+    #[test]
+    fn test_slide_options_report_file_lines() {
+        let err = parse_presentation(
+            "---\ntitle: Talk\n---\n# One\n<!-- end_slide -->\n---\ntheme: Mine\nsize: big\n---\n",
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "line 8: unknown slide option `size`");
+
+        let (_, slides) =
+            parse_presentation("# One\n<!-- end_slide -->\n---\ntheme: Mine\n---\n").unwrap();
+        let err = check_slide_themes(&slides, &Theme::built_in()).unwrap_err();
+        assert_eq!(err.to_string(), "line 3: unknown theme `Mine`");
+    }
+
+    #[test]
+    fn test_images_that_fail_to_load_are_reported() {
+        let (_, slides) = parse_presentation(
+            "- ![a](a.png)\n<!-- end_slide -->\n> ![b](b.png)\n\n![a](a.png)\n\n![c](https://example.com/c.png)",
+        )
+        .unwrap();
+        let (images, error) = load_images(Path::new("/nonexistent/talk.md"), &slides);
+        assert!(images.is_empty());
+        let error = error.unwrap();
+        assert!(
+            error.starts_with("Error reading image /nonexistent/a.png: "),
+            "{}",
+            error
+        );
+        assert!(error.ends_with(" (and 1 more)"), "{}", error);
+    }
 }
@@ -0,0 +1,1002 @@
+use crate::{
+    colors,
+    front_matter::{self, Entry, Value},
+    layout::{Alignment, VerticalAlignment},
+};
+use pulldown_cmark::{BlockQuoteKind, CodeBlockKind, Event, Options, Parser, Tag};
+use std::{iter::Peekable, time::Duration};
+use termion::color::Rgb;
+
+/// A slide parsed into a tree of markdown blocks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Slide {
+    pub blocks: Vec<Block>,
+    /// Generated from the metadata rather than written in markdown.
+    pub is_title_slide: bool,
+    pub options: SlideOptions,
+}
+
+impl Slide {
+    pub fn parse(content: &str) -> Slide {
+        Slide {
+            blocks: parse_blocks(content),
+            is_title_slide: false,
+            options: SlideOptions::default(),
+        }
+    }
+
+    /// Parses a slide that may start with an options block written like the
+    /// front matter. A leading `---` whose block doesn't hold `key: value`
+    /// lines is a rule. `content` starts at line `first_line` of the file.
+    pub fn parse_with_options(
+        content: &str,
+        first_line: usize,
+    ) -> Result<Slide, front_matter::Error> {
+        let start = content.len() - content.trim_start_matches(['\n', '\r', ' ']).len();
+        let start = content[..start].rfind('\n').map_or(0, |end| end + 1);
+        let first_line = first_line + content[..start].matches('\n').count();
+        let (entries, content) = front_matter::split_options(&content[start..], first_line)?;
+        Ok(Slide {
+            options: SlideOptions::from_entries(entries, first_line)?,
+            ..Slide::parse(content)
+        })
+    }
+
+    pub fn title_slide() -> Slide {
+        Slide {
+            blocks: Vec::new(),
+            is_title_slide: true,
+            options: SlideOptions::default(),
+        }
+    }
+
+    pub fn speaker_notes(&self) -> Vec<&str> {
+        self.blocks
+            .iter()
+            .filter_map(|block| match block {
+                Block::Directive(Directive::SpeakerNote(note)) => Some(note.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The text of all blocks and speaker notes without formatting, a line
+    /// per paragraph, heading, list item, code line or table row.
+    pub fn plain_text(&self) -> String {
+        let mut text = String::new();
+        add_plain_text(&self.blocks, &mut text);
+        text
+    }
+
+    /// The URLs of the images on the slide, also those in lists and quotes.
+    pub fn image_urls(&self) -> Vec<&str> {
+        let mut urls = Vec::new();
+        add_image_urls(&self.blocks, &mut urls);
+        urls
+    }
+
+    /// The number of reveal steps, one more than the number of pauses.
+    pub fn steps(&self) -> usize {
+        count_pauses(&self.blocks) + 1
+    }
+
+    /// The blocks revealed at `step`, i.e. everything before the pause with
+    /// the index `step`.
+    pub fn blocks_until_step(&self, step: usize) -> Vec<Block> {
+        let mut remaining_pauses = step;
+        blocks_until_pause(&self.blocks, &mut remaining_pauses).0
+    }
+}
+
+/// Settings that change how a single slide is shown.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SlideOptions {
+    /// The name of a theme to use instead of the current one.
+    pub theme: Option<String>,
+    pub alignment: Option<Alignment>,
+    pub vertical_alignment: Option<VerticalAlignment>,
+    pub hide_footer: bool,
+    pub layout: SlideLayout,
+    pub transition: Option<Transition>,
+    /// The time the presenter plans to spend on the slide.
+    pub time_budget: Option<Duration>,
+    pub background: Option<Rgb>,
+    /// The line the options start at.
+    pub line: usize,
+}
+
+impl SlideOptions {
+    fn from_entries(entries: Vec<Entry>, line: usize) -> Result<SlideOptions, front_matter::Error> {
+        let mut options = SlideOptions {
+            line,
+            ..SlideOptions::default()
+        };
+        for entry in entries {
+            let Value::String(value) = entry.value else {
+                return Err(front_matter::Error::new(
+                    entry.line,
+                    format!("`{}` must be a single value", entry.key),
+                ));
+            };
+            let invalid = |expected: &str| {
+                front_matter::Error::new(
+                    entry.line,
+                    format!("invalid {} `{}`, expected {}", entry.key, value, expected),
+                )
+            };
+            match entry.key.as_str() {
+                "theme" => options.theme = Some(value.clone()),
+                "alignment" => {
+                    options.alignment = Some(
+                        Alignment::parse(&value).ok_or_else(|| invalid("left, center or right"))?,
+                    )
+                }
+                "vertical_alignment" => {
+                    options.vertical_alignment = Some(
+                        VerticalAlignment::parse(&value).ok_or_else(|| invalid("top or center"))?,
+                    )
+                }
+                "hide_footer" => {
+                    options.hide_footer = match value.as_str() {
+                        "true" | "yes" => true,
+                        "false" | "no" => false,
+                        _ => return Err(invalid("true or false")),
+                    }
+                }
+                "layout" => {
+                    options.layout = match value.as_str() {
+                        "default" => SlideLayout::Default,
+                        "blank" => SlideLayout::Blank,
+                        "section" => SlideLayout::Section,
+                        _ => return Err(invalid("default, blank or section")),
+                    }
+                }
+                "transition" => {
+                    options.transition = match value.as_str() {
+                        "none" => None,
+                        "slide" => Some(Transition::Slide),
+                        "wipe" => Some(Transition::Wipe),
+                        _ => return Err(invalid("none, slide or wipe")),
+                    }
+                }
+                "time" => {
+                    options.time_budget = Some(
+                        parse_duration(&value).ok_or_else(|| invalid("e.g. 90s, 2m or 1:30"))?,
+                    )
+                }
+                "background" => {
+                    options.background =
+                        Some(colors::parse_hex(&value).ok_or_else(|| invalid("#rrggbb"))?)
+                }
+                key => {
+                    return Err(front_matter::Error::new(
+                        entry.line,
+                        format!("unknown slide option `{}`", key),
+                    ))
+                }
+            }
+        }
+        Ok(options)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum SlideLayout {
+    /// Content below the presentation title.
+    #[default]
+    Default,
+    /// Content without the presentation title.
+    Blank,
+    /// Content centred on an otherwise empty slide, e.g. for section dividers.
+    Section,
+}
+
+/// An animation played when the slide is entered.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transition {
+    /// The content moves in from the right.
+    Slide,
+    /// The content is revealed from the top down.
+    Wipe,
+}
+
+/// Parses `90s`, `2m`, `1m30s` or `1:30`.
+fn parse_duration(value: &str) -> Option<Duration> {
+    if value.is_empty() {
+        return None;
+    }
+    if let Some((minutes, seconds)) = value.split_once(':') {
+        let seconds: u64 = seconds.parse().ok().filter(|seconds| *seconds < 60)?;
+        return Some(Duration::from_secs(
+            minutes.parse::<u64>().ok()? * 60 + seconds,
+        ));
+    }
+    let (minutes, seconds) = match value.split_once('m') {
+        Some((minutes, seconds)) => (minutes.parse::<u64>().ok()?, seconds),
+        None => (0, value),
+    };
+    let seconds = match seconds {
+        "" => 0,
+        seconds => seconds.strip_suffix('s')?.parse::<u64>().ok()?,
+    };
+    Some(Duration::from_secs(minutes * 60 + seconds))
+}
+
+fn add_image_urls<'a>(blocks: &'a [Block], urls: &mut Vec<&'a str>) {
+    for block in blocks {
+        match block {
+            Block::Image { url, .. } => urls.push(url),
+            Block::List(list) => list
+                .items
+                .iter()
+                .for_each(|item| add_image_urls(item, urls)),
+            Block::BlockQuote { blocks, .. } => add_image_urls(blocks, urls),
+            _ => {}
+        }
+    }
+}
+
+fn add_plain_text(blocks: &[Block], text: &mut String) {
+    for block in blocks {
+        let line = match block {
+            Block::Heading { content, .. } | Block::Paragraph(content) => {
+                Inline::plain_text(content)
+            }
+            Block::CodeBlock { code, .. } => String::from(code.trim_end()),
+            Block::Table(table) => std::iter::once(&table.header)
+                .chain(&table.rows)
+                .map(|row| {
+                    let cells: Vec<String> =
+                        row.iter().map(|cell| Inline::plain_text(cell)).collect();
+                    cells.join(" ")
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Block::Image { alt, .. } => alt.clone(),
+            Block::Directive(Directive::SpeakerNote(note)) => note.clone(),
+            Block::List(list) => {
+                for item in &list.items {
+                    add_plain_text(item, text);
+                }
+                continue;
+            }
+            Block::BlockQuote { blocks, .. } => {
+                add_plain_text(blocks, text);
+                continue;
+            }
+            Block::ThematicBreak | Block::Directive(_) => continue,
+        };
+        text.push_str(&line);
+        text.push('\n');
+    }
+}
+
+fn count_pauses(blocks: &[Block]) -> usize {
+    blocks
+        .iter()
+        .map(|block| match block {
+            Block::Directive(Directive::Pause) => 1,
+            Block::List(list) => list.items.iter().map(|item| count_pauses(item)).sum(),
+            Block::BlockQuote { blocks, .. } => count_pauses(blocks),
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Returns the blocks before the pause after `remaining_pauses` pauses and
+/// whether that pause was reached.
+fn blocks_until_pause(blocks: &[Block], remaining_pauses: &mut usize) -> (Vec<Block>, bool) {
+    let mut revealed = Vec::new();
+    for block in blocks {
+        let (block, reached) = match block {
+            Block::Directive(Directive::Pause) if *remaining_pauses == 0 => {
+                return (revealed, true)
+            }
+            Block::Directive(Directive::Pause) => {
+                *remaining_pauses -= 1;
+                (block.clone(), false)
+            }
+            Block::List(list) => {
+                let mut items = Vec::new();
+                let mut reached = false;
+                for item in &list.items {
+                    let (item, item_reached) = blocks_until_pause(item, remaining_pauses);
+                    items.push(item);
+                    if item_reached {
+                        reached = true;
+                        break;
+                    }
+                }
+                (
+                    Block::List(List {
+                        start: list.start,
+                        items,
+                    }),
+                    reached,
+                )
+            }
+            Block::BlockQuote { kind, blocks } => {
+                let (blocks, reached) = blocks_until_pause(blocks, remaining_pauses);
+                (
+                    Block::BlockQuote {
+                        kind: *kind,
+                        blocks,
+                    },
+                    reached,
+                )
+            }
+            block => (block.clone(), false),
+        };
+        revealed.push(block);
+        if reached {
+            return (revealed, true);
+        }
+    }
+    (revealed, false)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Block {
+    Heading {
+        level: u8,
+        content: Vec<Inline>,
+    },
+    Paragraph(Vec<Inline>),
+    List(List),
+    CodeBlock {
+        language: Option<String>,
+        code: String,
+        /// Set by a `+line_numbers` attribute after the language.
+        line_numbers: bool,
+    },
+    BlockQuote {
+        /// Set for GitHub-style callouts like `> [!NOTE]`.
+        kind: Option<Admonition>,
+        blocks: Vec<Block>,
+    },
+    Table(Table),
+    /// A paragraph made up of a single image.
+    Image {
+        url: String,
+        alt: String,
+    },
+    ThematicBreak,
+    Directive(Directive),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Admonition {
+    Note,
+    Tip,
+    Important,
+    Warning,
+    Caution,
+}
+
+/// A command given in an HTML comment, e.g. `<!-- pause -->`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Directive {
+    Pause,
+    /// `<!-- speaker_note: ... -->`, possibly spanning several lines.
+    SpeakerNote(String),
+    /// `<!-- column_layout: [2, 1] -->` splits the width into columns with
+    /// these proportions.
+    ColumnLayout(Vec<u16>),
+    /// `<!-- column: 0 -->` puts the following blocks into a column.
+    Column(usize),
+    /// `<!-- reset_layout -->` goes back to the full width.
+    ResetLayout,
+    /// `<!-- jump_to_middle -->` centres the following blocks vertically.
+    JumpToMiddle,
+}
+
+impl Directive {
+    fn parse(html: &str) -> Option<Directive> {
+        let comment = html
+            .trim()
+            .strip_prefix("<!--")?
+            .strip_suffix("-->")?
+            .trim();
+        if let Some(note) = comment.strip_prefix("speaker_note:") {
+            let note = note
+                .trim()
+                .lines()
+                .map(str::trim)
+                .collect::<Vec<_>>()
+                .join("\n");
+            return Some(Directive::SpeakerNote(note));
+        }
+        if let Some(ratios) = comment.strip_prefix("column_layout:") {
+            let ratios = ratios
+                .trim()
+                .strip_prefix('[')?
+                .strip_suffix(']')?
+                .split(',')
+                .map(|ratio| ratio.trim().parse().ok().filter(|ratio| *ratio > 0))
+                .collect::<Option<Vec<u16>>>()?;
+            return Some(Directive::ColumnLayout(ratios));
+        }
+        if let Some(column) = comment.strip_prefix("column:") {
+            return column.trim().parse().ok().map(Directive::Column);
+        }
+        match comment {
+            "pause" => Some(Directive::Pause),
+            "reset_layout" => Some(Directive::ResetLayout),
+            "jump_to_middle" => Some(Directive::JumpToMiddle),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct List {
+    /// The number of the first item for ordered lists, `None` for bullet lists.
+    pub start: Option<u64>,
+    pub items: Vec<Vec<Block>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnAlignment {
+    None,
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    pub alignments: Vec<ColumnAlignment>,
+    pub header: Vec<Vec<Inline>>,
+    pub rows: Vec<Vec<Vec<Inline>>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Inline {
+    Text(String),
+    Code(String),
+    Emphasis(Vec<Inline>),
+    Strong(Vec<Inline>),
+    Strikethrough(Vec<Inline>),
+    Link { url: String, content: Vec<Inline> },
+    Image { url: String, alt: String },
+    SoftBreak,
+    HardBreak,
+}
+
+impl Inline {
+    /// The text of the inline without any formatting.
+    pub fn plain_text(inlines: &[Inline]) -> String {
+        inlines
+            .iter()
+            .map(|inline| match inline {
+                Inline::Text(text) | Inline::Code(text) => text.clone(),
+                Inline::Emphasis(content)
+                | Inline::Strong(content)
+                | Inline::Strikethrough(content)
+                | Inline::Link { content, .. } => Inline::plain_text(content),
+                Inline::Image { alt, .. } => alt.clone(),
+                Inline::SoftBreak => String::from(" "),
+                Inline::HardBreak => String::from("\n"),
+            })
+            .collect()
+    }
+}
+
+pub fn parse_blocks(content: &str) -> Vec<Block> {
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_GFM;
+    let mut parser = SlideParser {
+        events: Parser::new_ext(content, options).peekable(),
+    };
+    parser.parse_blocks()
+}
+
+/// A paragraph, or an image block if the paragraph is a single image.
+fn paragraph(mut inlines: Vec<Inline>) -> Block {
+    match &mut inlines[..] {
+        [Inline::Image { url, alt }] => Block::Image {
+            url: std::mem::take(url),
+            alt: std::mem::take(alt),
+        },
+        _ => Block::Paragraph(inlines),
+    }
+}
+
+struct SlideParser<'a> {
+    events: Peekable<Parser<'a>>,
+}
+
+impl SlideParser<'_> {
+    /// Parses blocks until the end of the enclosing element (or the input).
+    fn parse_blocks(&mut self) -> Vec<Block> {
+        let mut blocks = Vec::new();
+        while let Some(event) = self.events.peek() {
+            match event {
+                Event::End(_) => {
+                    self.events.next();
+                    break;
+                }
+                // Tight list items contain their text without a paragraph.
+                event if is_inline(event) => {
+                    let inlines = self.parse_inline_run();
+                    if !inlines.is_empty() {
+                        blocks.push(paragraph(inlines));
+                    }
+                }
+                _ => {
+                    let event = self.events.next().unwrap();
+                    if let Some(block) = self.parse_block(event) {
+                        blocks.push(block);
+                    }
+                }
+            }
+        }
+        blocks
+    }
+
+    fn parse_block(&mut self, event: Event) -> Option<Block> {
+        match event {
+            Event::Start(Tag::Paragraph) => Some(paragraph(self.parse_inlines())),
+            Event::Start(Tag::Heading { level, .. }) => Some(Block::Heading {
+                level: level as u8,
+                content: self.parse_inlines(),
+            }),
+            Event::Start(Tag::BlockQuote(kind)) => Some(Block::BlockQuote {
+                kind: kind.map(|kind| match kind {
+                    BlockQuoteKind::Note => Admonition::Note,
+                    BlockQuoteKind::Tip => Admonition::Tip,
+                    BlockQuoteKind::Important => Admonition::Important,
+                    BlockQuoteKind::Warning => Admonition::Warning,
+                    BlockQuoteKind::Caution => Admonition::Caution,
+                }),
+                blocks: self.parse_blocks(),
+            }),
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let info = match kind {
+                    CodeBlockKind::Fenced(info) => info.to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                let mut attributes = info.split_whitespace();
+                Some(Block::CodeBlock {
+                    language: attributes.next().map(|language| language.to_string()),
+                    line_numbers: attributes.any(|attribute| attribute == "+line_numbers"),
+                    code: self.collect_text(),
+                })
+            }
+            Event::Start(Tag::List(start)) => {
+                let mut items = Vec::new();
+                while let Some(event) = self.events.next() {
+                    match event {
+                        Event::Start(Tag::Item) => items.push(self.parse_blocks()),
+                        Event::End(_) => break,
+                        _ => {}
+                    }
+                }
+                Some(Block::List(List { start, items }))
+            }
+            Event::Start(Tag::Table(alignments)) => Some(Block::Table(
+                self.parse_table(
+                    alignments
+                        .into_iter()
+                        .map(|alignment| match alignment {
+                            pulldown_cmark::Alignment::None => ColumnAlignment::None,
+                            pulldown_cmark::Alignment::Left => ColumnAlignment::Left,
+                            pulldown_cmark::Alignment::Center => ColumnAlignment::Center,
+                            pulldown_cmark::Alignment::Right => ColumnAlignment::Right,
+                        })
+                        .collect(),
+                ),
+            )),
+            Event::Start(Tag::HtmlBlock) => {
+                Directive::parse(&self.collect_text()).map(Block::Directive)
+            }
+            Event::Rule => Some(Block::ThematicBreak),
+            Event::Start(_) => {
+                self.skip_element();
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn parse_table(&mut self, alignments: Vec<ColumnAlignment>) -> Table {
+        let mut table = Table {
+            alignments,
+            header: Vec::new(),
+            rows: Vec::new(),
+        };
+        while let Some(event) = self.events.next() {
+            match event {
+                Event::Start(Tag::TableHead) => table.header = self.parse_table_cells(),
+                Event::Start(Tag::TableRow) => {
+                    let row = self.parse_table_cells();
+                    table.rows.push(row);
+                }
+                Event::End(_) => break,
+                _ => {}
+            }
+        }
+        table
+    }
+
+    fn parse_table_cells(&mut self) -> Vec<Vec<Inline>> {
+        let mut cells = Vec::new();
+        while let Some(event) = self.events.next() {
+            match event {
+                Event::Start(Tag::TableCell) => cells.push(self.parse_inlines()),
+                Event::End(_) => break,
+                _ => {}
+            }
+        }
+        cells
+    }
+
+    /// Parses inlines until the end of the enclosing element.
+    fn parse_inlines(&mut self) -> Vec<Inline> {
+        let mut inlines = Vec::new();
+        while let Some(event) = self.events.next() {
+            if let Event::End(_) = event {
+                break;
+            }
+            if let Some(inline) = self.parse_inline(event) {
+                inlines.push(inline);
+            }
+        }
+        inlines
+    }
+
+    /// Parses inlines as long as no block element starts or ends.
+    fn parse_inline_run(&mut self) -> Vec<Inline> {
+        let mut inlines = Vec::new();
+        while self.events.peek().is_some_and(is_inline) {
+            let event = self.events.next().unwrap();
+            if let Some(inline) = self.parse_inline(event) {
+                inlines.push(inline);
+            }
+        }
+        inlines
+    }
+
+    fn parse_inline(&mut self, event: Event) -> Option<Inline> {
+        match event {
+            Event::Text(text) => Some(Inline::Text(text.to_string())),
+            Event::Code(code) => Some(Inline::Code(code.to_string())),
+            Event::SoftBreak => Some(Inline::SoftBreak),
+            Event::HardBreak => Some(Inline::HardBreak),
+            Event::Start(Tag::Emphasis) => Some(Inline::Emphasis(self.parse_inlines())),
+            Event::Start(Tag::Strong) => Some(Inline::Strong(self.parse_inlines())),
+            Event::Start(Tag::Strikethrough) => Some(Inline::Strikethrough(self.parse_inlines())),
+            Event::Start(Tag::Link { dest_url, .. }) => Some(Inline::Link {
+                url: dest_url.to_string(),
+                content: self.parse_inlines(),
+            }),
+            Event::Start(Tag::Image { dest_url, .. }) => Some(Inline::Image {
+                url: dest_url.to_string(),
+                alt: Inline::plain_text(&self.parse_inlines()),
+            }),
+            Event::Start(_) => {
+                self.skip_element();
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn collect_text(&mut self) -> String {
+        let mut text = String::new();
+        for event in self.events.by_ref() {
+            match event {
+                Event::Text(content) | Event::Html(content) => text.push_str(&content),
+                Event::End(_) => break,
+                _ => {}
+            }
+        }
+        text
+    }
+
+    /// Skips all events up to and including the end of the current element.
+    fn skip_element(&mut self) {
+        let mut depth = 1;
+        for event in self.events.by_ref() {
+            match event {
+                Event::Start(_) => depth += 1,
+                Event::End(_) => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                break;
+            }
+        }
+    }
+}
+
+fn is_inline(event: &Event) -> bool {
+    match event {
+        Event::Text(_)
+        | Event::Code(_)
+        | Event::SoftBreak
+        | Event::HardBreak
+        | Event::InlineHtml(_)
+        | Event::InlineMath(_)
+        | Event::FootnoteReference(_) => true,
+        Event::Start(tag) => matches!(
+            tag,
+            Tag::Emphasis
+                | Tag::Strong
+                | Tag::Strikethrough
+                | Tag::Superscript
+                | Tag::Subscript
+                | Tag::Link { .. }
+                | Tag::Image { .. }
+        ),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str) -> Inline {
+        Inline::Text(String::from(text))
+    }
+
+    #[test]
+    fn test_parse_headings_and_paragraphs() {
+        let blocks = parse_blocks("## Title\n\nSome text");
+        assert_eq!(
+            blocks,
+            vec![
+                Block::Heading {
+                    level: 2,
+                    content: vec![text("Title")]
+                },
+                Block::Paragraph(vec![text("Some text")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_inline_emphasis() {
+        let blocks = parse_blocks("*a* **b** `c` ~~d~~");
+        assert_eq!(
+            blocks,
+            vec![Block::Paragraph(vec![
+                Inline::Emphasis(vec![text("a")]),
+                text(" "),
+                Inline::Strong(vec![text("b")]),
+                text(" "),
+                Inline::Code(String::from("c")),
+                text(" "),
+                Inline::Strikethrough(vec![text("d")]),
+            ])]
+        );
+    }
+
+    #[test]
+    fn test_parse_tight_list_items_as_paragraphs() {
+        let blocks = parse_blocks("- one\n- two\n  - nested");
+        assert_eq!(
+            blocks,
+            vec![Block::List(List {
+                start: None,
+                items: vec![
+                    vec![Block::Paragraph(vec![text("one")])],
+                    vec![
+                        Block::Paragraph(vec![text("two")]),
+                        Block::List(List {
+                            start: None,
+                            items: vec![vec![Block::Paragraph(vec![text("nested")])]],
+                        }),
+                    ],
+                ],
+            })]
+        );
+    }
+
+    #[test]
+    fn test_parse_fenced_code_block() {
+        let blocks = parse_blocks("```rust title\nfn main() {}\n```");
+        assert_eq!(
+            blocks,
+            vec![Block::CodeBlock {
+                language: Some(String::from("rust")),
+                code: String::from("fn main() {}\n"),
+                line_numbers: false,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_code_block_line_numbers() {
+        let blocks = parse_blocks("```rust +line_numbers\nlet x = 1;\n```");
+        assert_eq!(
+            blocks,
+            vec![Block::CodeBlock {
+                language: Some(String::from("rust")),
+                code: String::from("let x = 1;\n"),
+                line_numbers: true,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_table() {
+        let blocks = parse_blocks("| a | b |\n|:--|--:|\n| 1 | 2 |");
+        assert_eq!(
+            blocks,
+            vec![Block::Table(Table {
+                alignments: vec![ColumnAlignment::Left, ColumnAlignment::Right],
+                header: vec![vec![text("a")], vec![text("b")]],
+                rows: vec![vec![vec![text("1")], vec![text("2")]]],
+            })]
+        );
+    }
+
+    #[test]
+    fn test_reveal_steps_at_pauses() {
+        let slide = Slide::parse("a\n\n<!-- pause -->\n\n- b\n  <!-- pause -->\n- c");
+        assert_eq!(slide.steps(), 3);
+        assert_eq!(
+            slide.blocks_until_step(0),
+            vec![Block::Paragraph(vec![text("a")])]
+        );
+        let Block::List(list) = &slide.blocks_until_step(1)[2] else {
+            panic!("expected a list");
+        };
+        assert_eq!(list.items, vec![vec![Block::Paragraph(vec![text("b")])]]);
+        assert_eq!(slide.blocks_until_step(2), slide.blocks);
+    }
+
+    #[test]
+    fn test_parse_speaker_notes() {
+        let slide = Slide::parse(
+            "<!-- speaker_note: short -->\n\ntext\n\n<!-- speaker_note:\n  first\n\n  second\n-->",
+        );
+        assert_eq!(slide.speaker_notes(), vec!["short", "first\n\nsecond"]);
+        assert_eq!(slide.blocks[1], Block::Paragraph(vec![text("text")]));
+    }
+
+    #[test]
+    fn test_plain_text() {
+        let slide = Slide::parse(
+            "# A *title*\n\n- item\n  > quoted\n\n```rust\nfn main() {}\n```\n\n| a | b |\n|---|---|\n| c | d |\n\n<!-- speaker_note: note -->",
+        );
+        assert_eq!(
+            slide.plain_text(),
+            "A title\nitem\nquoted\nfn main() {}\na b\nc d\nnote\n"
+        );
+    }
+
+    #[test]
+    fn test_skip_html_comments() {
+        let blocks = parse_blocks("<!-- comment -->\n\ntext");
+        assert_eq!(blocks, vec![Block::Paragraph(vec![text("text")])]);
+    }
+
+    #[test]
+    fn test_parse_slide_options() {
+        let slide = Slide::parse_with_options(
+            "\n---\nalignment: center\nvertical_alignment: center\nhide_footer: true\ntime: 1m30s\nbackground: \"#102030\"\n---\n# Part 2",
+            10,
+        )
+        .unwrap();
+        assert_eq!(slide.options.alignment, Some(Alignment::Center));
+        assert_eq!(
+            slide.options.vertical_alignment,
+            Some(VerticalAlignment::Center)
+        );
+        assert!(slide.options.hide_footer);
+        assert_eq!(slide.options.time_budget, Some(Duration::from_secs(90)));
+        assert_eq!(slide.options.background, Some(Rgb(16, 32, 48)));
+        assert_eq!(slide.options.line, 11);
+        assert_eq!(slide.blocks.len(), 1);
+
+        let err = Slide::parse_with_options("\n---\nlayout: wide\n---\n", 10).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 12: invalid layout `wide`, expected default, blank or section"
+        );
+    }
+
+    #[test]
+    fn test_leading_rule_is_not_an_options_block() {
+        let slide = Slide::parse_with_options("\n---\n\nSome text\n", 10).unwrap();
+        assert_eq!(
+            slide.options,
+            SlideOptions {
+                line: 11,
+                ..SlideOptions::default()
+            }
+        );
+        assert_eq!(
+            slide.blocks,
+            vec![
+                Block::ThematicBreak,
+                Block::Paragraph(vec![text("Some text")])
+            ]
+        );
+
+        let slide = Slide::parse_with_options("---\ntext\n---\n", 10).unwrap();
+        assert_eq!(slide.blocks.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("2m"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_duration("1:05"), Some(Duration::from_secs(65)));
+        assert_eq!(parse_duration("1:75"), None);
+        assert_eq!(parse_duration("soon"), None);
+    }
+
+    #[test]
+    fn test_parse_all_heading_levels_and_setext_headings() {
+        let heading = |level: u8, content: &str| Block::Heading {
+            level,
+            content: vec![text(content)],
+        };
+        assert_eq!(
+            parse_blocks("##### Five\n\n###### Six\n\nOne\n===\n\nTwo\n---\n\n#hashtag"),
+            vec![
+                heading(5, "Five"),
+                heading(6, "Six"),
+                heading(1, "One"),
+                heading(2, "Two"),
+                Block::Paragraph(vec![text("#hashtag")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_admonitions() {
+        assert_eq!(
+            parse_blocks("> [!WARNING]\n> Careful\n\n> plain"),
+            vec![
+                Block::BlockQuote {
+                    kind: Some(Admonition::Warning),
+                    blocks: vec![Block::Paragraph(vec![text("Careful")])],
+                },
+                Block::BlockQuote {
+                    kind: None,
+                    blocks: vec![Block::Paragraph(vec![text("plain")])],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_images() {
+        assert_eq!(
+            parse_blocks("![A diagram](images/diagram.png)\n\nSee ![icon](icon.png)"),
+            vec![
+                Block::Image {
+                    url: String::from("images/diagram.png"),
+                    alt: String::from("A diagram"),
+                },
+                Block::Paragraph(vec![
+                    text("See "),
+                    Inline::Image {
+                        url: String::from("icon.png"),
+                        alt: String::from("icon"),
+                    },
+                ]),
+            ]
+        );
+
+        let slide = Slide::parse("![a](a.png)\n\n- ![b](b.png)\n\n> ![c](c.png)\n> - ![d](d.png)");
+        assert_eq!(slide.image_urls(), vec!["a.png", "b.png", "c.png", "d.png"]);
+    }
+
+    #[test]
+    fn test_parse_layout_directives() {
+        assert_eq!(
+            parse_blocks("<!-- column_layout: [2, 1] -->\n<!-- column: 1 -->\n<!-- reset_layout -->\n<!-- column_layout: [0] -->\n<!-- jump_to_middle -->"),
+            vec![
+                Block::Directive(Directive::ColumnLayout(vec![2, 1])),
+                Block::Directive(Directive::Column(1)),
+                Block::Directive(Directive::ResetLayout),
+                Block::Directive(Directive::JumpToMiddle),
+            ]
+        );
+    }
+}
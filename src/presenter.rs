@@ -0,0 +1,201 @@
+use crate::events::Event;
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{
+        unix::{OwnedReadHalf, OwnedWriteHalf},
+        UnixListener, UnixStream,
+    },
+    sync::{mpsc::UnboundedSender, Mutex},
+};
+
+/// Keeps the audience and presenter views of a deck on the same slide by
+/// exchanging `goto <slide> <step>` lines over a Unix socket.
+pub struct PresenterLink {
+    /// The connections to the other views, tagged with their peer number.
+    writers: Arc<Mutex<Vec<(usize, OwnedWriteHalf)>>>,
+    /// The socket to clean up, set for the listening side only.
+    socket: Option<PathBuf>,
+}
+
+/// The socket shared by all views of `presentation_file`.
+pub fn socket_path(presentation_file: &Path) -> PathBuf {
+    let path = fs::canonicalize(presentation_file).unwrap_or(presentation_file.to_path_buf());
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    std::env::temp_dir().join(format!("term_deck-{:x}.sock", hasher.finish()))
+}
+
+impl PresenterLink {
+    /// Accepts presenter views, used by the audience view.
+    pub fn listen(socket: &Path, events: UnboundedSender<Event>) -> io::Result<PresenterLink> {
+        match std::os::unix::net::UnixStream::connect(socket) {
+            Ok(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    "the presentation is already running in another terminal",
+                ))
+            }
+            // Nothing listens on a socket left over by a crashed audience view,
+            // which would make bind fail.
+            Err(err) if err.kind() == io::ErrorKind::ConnectionRefused => fs::remove_file(socket)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+        let listener = UnixListener::bind(socket)?;
+        let writers: Arc<Mutex<Vec<(usize, OwnedWriteHalf)>>> = Arc::default();
+        let accepted_writers = Arc::clone(&writers);
+        tokio::spawn(async move {
+            let mut peers = 0..;
+            while let Ok((stream, _)) = listener.accept().await {
+                let peer = peers.next().unwrap();
+                let (reader, writer) = stream.into_split();
+                accepted_writers.lock().await.push((peer, writer));
+                spawn_reader(reader, peer, events.clone());
+                if events.send(Event::PeerConnected).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(PresenterLink {
+            writers,
+            socket: Some(socket.to_path_buf()),
+        })
+    }
+
+    /// Connects to a running audience view, used by the presenter view.
+    pub async fn connect(
+        socket: &Path,
+        events: UnboundedSender<Event>,
+    ) -> io::Result<PresenterLink> {
+        let (reader, writer) = UnixStream::connect(socket).await?.into_split();
+        spawn_reader(reader, 0, events);
+        Ok(PresenterLink {
+            writers: Arc::new(Mutex::new(vec![(0, writer)])),
+            socket: None,
+        })
+    }
+
+    /// Sends the position to every connected view but `except`, the one a
+    /// relayed move came from.
+    pub async fn send_position(&self, slide: usize, step: usize, except: Option<usize>) {
+        let message = format!("goto {} {}\n", slide, step);
+        let mut writers = self.writers.lock().await;
+        let mut connected = Vec::new();
+        for (peer, mut writer) in writers.drain(..) {
+            if Some(peer) == except || writer.write_all(message.as_bytes()).await.is_ok() {
+                connected.push((peer, writer));
+            }
+        }
+        *writers = connected;
+    }
+}
+
+impl Drop for PresenterLink {
+    fn drop(&mut self) {
+        if let Some(socket) = &self.socket {
+            let _ = fs::remove_file(socket);
+        }
+    }
+}
+
+fn spawn_reader(reader: OwnedReadHalf, peer: usize, events: UnboundedSender<Event>) {
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if let Some(event) = parse_message(&line, peer) {
+                if events.send(event).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+}
+
+fn parse_message(line: &str, peer: usize) -> Option<Event> {
+    let mut parts = line.split_whitespace();
+    match (parts.next()?, parts.next()?, parts.next()?) {
+        ("goto", slide, step) => Some(Event::Goto {
+            slide: slide.parse().ok()?,
+            step: step.parse().ok()?,
+            peer,
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_goto_message() {
+        assert!(matches!(
+            parse_message("goto 3 1", 2),
+            Some(Event::Goto {
+                slide: 3,
+                step: 1,
+                peer: 2
+            })
+        ));
+        assert!(parse_message("goto 3", 0).is_none());
+        assert!(parse_message("jump 3 1", 0).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_listen_only_once_per_socket() {
+        let socket =
+            std::env::temp_dir().join(format!("term_deck-test-{}.sock", std::process::id()));
+        let (sender, _events) = tokio::sync::mpsc::unbounded_channel();
+        // A socket nobody listens on anymore is replaced.
+        drop(std::os::unix::net::UnixListener::bind(&socket).unwrap());
+        let link = PresenterLink::listen(&socket, sender.clone()).unwrap();
+        let err = PresenterLink::listen(&socket, sender.clone())
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+        drop(link);
+        assert!(!socket.exists());
+    }
+
+    #[tokio::test]
+    async fn test_relay_skips_the_sender() {
+        let socket =
+            std::env::temp_dir().join(format!("term_deck-test-relay-{}.sock", std::process::id()));
+        let (sender, mut events) = tokio::sync::mpsc::unbounded_channel();
+        let audience = PresenterLink::listen(&socket, sender).unwrap();
+        let (first_sender, mut first_events) = tokio::sync::mpsc::unbounded_channel();
+        let first = PresenterLink::connect(&socket, first_sender).await.unwrap();
+        let (second_sender, mut second_events) = tokio::sync::mpsc::unbounded_channel();
+        let _second = PresenterLink::connect(&socket, second_sender)
+            .await
+            .unwrap();
+        for _ in 0..2 {
+            assert!(matches!(events.recv().await, Some(Event::PeerConnected)));
+        }
+
+        first.send_position(2, 1, None).await;
+        let Some(Event::Goto { slide, step, peer }) = events.recv().await else {
+            panic!("expected a goto");
+        };
+        audience.send_position(slide, step, Some(peer)).await;
+        assert!(matches!(
+            second_events.recv().await,
+            Some(Event::Goto {
+                slide: 2,
+                step: 1,
+                ..
+            })
+        ));
+        let echo = tokio::time::timeout(Duration::from_millis(100), first_events.recv()).await;
+        assert!(echo.is_err());
+    }
+}
@@ -1,6 +1,19 @@
-use crate::{colors::Theme, Presentation};
+use crate::{
+    colors::{Theme, ThemeColors},
+    figlet::Font,
+    graphics, highlighting,
+    image::Image,
+    keymap::Prompt,
+    layout::{self, Alignment, Area, Grid, VerticalAlignment},
+    markdown::{
+        Admonition, Block, ColumnAlignment, Directive, Inline, List, SlideLayout, Table, Transition,
+    },
+    terminal,
+    text::{display_width, Line, Span, Style},
+    Presentation, TitleSlideLayout,
+};
 use std::{
-    fmt::Display,
+    collections::HashMap,
     io::{stdout, Write},
     ops::Add,
     thread,
@@ -8,194 +21,1233 @@ use std::{
 };
 use termion::{
     color::{self, Rgb},
-    cursor::{self, DetectCursorPos},
+    cursor,
     raw::IntoRawMode,
-    style, terminal_size,
+    terminal_size,
 };
 
+#[derive(Clone, Copy)]
 enum Header {
     Header1,
     Header2,
     Header3,
     Header4,
+    Header5,
+    Header6,
 }
 
 impl Header {
-    fn color(&self, theme: &Theme) -> color::Rgb {
-        match self {
-            Header::Header1 => theme.get_theme_colors().primary,
-            Header::Header2 => theme.get_theme_colors().secondary,
-            Header::Header3 => theme.get_theme_colors().tertiary,
-            Header::Header4 => theme.get_theme_colors().accent,
+    fn style(&self, theme: &Theme) -> Style {
+        let heading = theme.get_theme_colors().headings[*self as usize];
+        Style {
+            bold: heading.bold,
+            italic: heading.italic,
+            underline: heading.underline,
+            ..Style::fg(heading.color)
         }
     }
 
-    fn header_by_prefix(prefix: &str) -> Option<Header> {
-        match prefix {
-            "#" => Some(Header::Header1),
-            "##" => Some(Header::Header2),
-            "###" => Some(Header::Header3),
-            "####" => Some(Header::Header4),
-            _ => None,
+    fn header_by_level(level: u8) -> Header {
+        match level {
+            1 => Header::Header1,
+            2 => Header::Header2,
+            3 => Header::Header3,
+            4 => Header::Header4,
+            5 => Header::Header5,
+            _ => Header::Header6,
         }
     }
 }
 
+/// The empty columns between the columns of a column layout.
+const COLUMN_GAP: usize = 2;
+
+pub const TRANSITION_FRAMES: u16 = 12;
+pub const TRANSITION_FRAME_TIME: Duration = Duration::from_millis(20);
+
 pub fn render_slide(
     presentation: &Presentation,
     stdout: &mut termion::raw::RawTerminal<std::io::Stdout>,
 ) {
-    write!(stdout, "{}{}", termion::clear::All, cursor::Goto(1, 1)).unwrap();
-    render_text_centered(
-        presentation
-            .metadata
-            .title
-            .as_ref()
-            .unwrap_or(&String::from("No title found")),
-        false,
+    render_slide_frame(presentation, None, stdout);
+    stdout.flush().unwrap();
+}
+
+/// Renders frame `frame` of `TRANSITION_FRAMES` of the content of the
+/// current slide coming in.
+pub fn render_transition_frame(
+    presentation: &Presentation,
+    transition: Transition,
+    frame: u16,
+    stdout: &mut termion::raw::RawTerminal<std::io::Stdout>,
+) {
+    let progress = frame as f32 / TRANSITION_FRAMES as f32;
+    render_slide_frame(presentation, Some((transition, progress)), stdout);
+    stdout.flush().unwrap();
+}
+
+/// Renders the current slide, with its content part way through a transition
+/// if `transition` is given.
+fn render_slide_frame(
+    presentation: &Presentation,
+    transition: Option<(Transition, f32)>,
+    stdout: &mut termion::raw::RawTerminal<std::io::Stdout>,
+) {
+    let slide = presentation.current_slide();
+    let options = &slide.options;
+    let theme = presentation.slide_theme();
+    let colors = theme.get_theme_colors();
+    let background = options.background.or(colors.background);
+    let (width, height) = terminal_size().unwrap();
+    if layout::is_too_small(width, height) {
+        render_terminal_too_small(width, height, stdout);
+        return;
+    }
+    clear_screen(background, stdout);
+    let mut area = Area::slide_content(width, height, presentation.margin());
+    if slide.is_title_slide {
+        render_title_slide(presentation, area, stdout);
+    } else {
+        if options.layout == SlideLayout::Default {
+            render_aligned_text(
+                &presentation
+                    .metadata
+                    .title
+                    .as_deref()
+                    .unwrap_or("No title found")
+                    .replace('\n', " "),
+                1,
+                Alignment::Center,
+                with_background(Style::fg(colors.primary).bold(), background),
+                stdout,
+            );
+        }
+        let section = options.layout == SlideLayout::Section;
+        if section {
+            // The whole screen above the footer.
+            area.y = 1;
+            area.height = height.saturating_sub(2);
+        }
+        let font = presentation.font.as_ref();
+        let content_width = area.width as usize;
+        let blocks = slide.blocks_until_step(presentation.current_step);
+        let middle = blocks
+            .iter()
+            .position(|block| matches!(block, Block::Directive(Directive::JumpToMiddle)));
+        let render_content = |max_rows: usize| {
+            let images = Images {
+                images: Some(&presentation.images),
+                cache: Some(&presentation.graphics),
+                max_rows,
+            };
+            match middle {
+                Some(middle) => {
+                    let mut lines =
+                        render_blocks(&blocks[..middle], theme, font, images, content_width);
+                    let rest =
+                        render_blocks(&blocks[middle + 1..], theme, font, images, content_width);
+                    // Centred, but below the content before the directive.
+                    let top = VerticalAlignment::Center
+                        .offset(rest.len(), area.height as usize)
+                        .max(lines.len() + usize::from(!lines.is_empty()));
+                    lines.resize(top, Line::default());
+                    lines.extend(rest);
+                    lines
+                }
+                None => render_blocks(&blocks, theme, font, images, content_width),
+            }
+        };
+        let available = area.height as usize;
+        let mut max_rows = available;
+        let mut lines = render_content(max_rows);
+        // Shrink the images to make room for the text around them.
+        while lines.len() > available && max_rows > 1 {
+            max_rows = max_rows.saturating_sub(lines.len() - available).max(1);
+            lines = render_content(max_rows);
+        }
+        if let Some(query) = &presentation.search {
+            let style = Style::fg(colors.background.unwrap_or(colors.code_background))
+                .background(colors.accent);
+            lines = lines
+                .into_iter()
+                .map(|line| line.highlight(query, style))
+                .collect();
+        }
+        let (shown, hidden) = layout::cut_off(lines.len(), available);
+        if hidden > 0 {
+            lines.truncate(shown);
+            lines.push(Line::new(vec![Span::new(
+                format!("⋯ {} more lines", hidden),
+                Style::fg(colors.line_number),
+            )]));
+        }
+        let metadata = &presentation.metadata;
+        let alignment = options
+            .alignment
+            .or(section.then_some(Alignment::Center))
+            .or(metadata.alignment)
+            .unwrap_or_default();
+        let vertical_alignment = match middle {
+            Some(_) => VerticalAlignment::Top,
+            None => options
+                .vertical_alignment
+                .or(section.then_some(VerticalAlignment::Center))
+                .or(metadata.vertical_alignment)
+                .unwrap_or_default(),
+        };
+        let top = vertical_alignment.offset(lines.len(), area.height as usize) as u16;
+        area.y += top;
+        area.height -= top;
+        match transition {
+            Some((Transition::Slide, progress)) => {
+                let offset = (area.width as f32 * (1.0 - progress)) as u16;
+                area.x += offset;
+                area.width -= offset;
+            }
+            Some((Transition::Wipe, progress)) => {
+                lines.truncate((lines.len() as f32 * progress).ceil() as usize);
+            }
+            None => {}
+        }
+        render_aligned_lines(&lines, area, alignment, background, stdout);
+    }
+    if options.hide_footer {
+        return;
+    }
+    let steps = slide.steps();
+    let step_counter = match steps {
+        1 => String::new(),
+        steps => format!(" ({}/{})", presentation.current_step + 1, steps),
+    };
+    render_aligned_text(
+        &format!(
+            "{}/{} slides{}",
+            presentation.current_slide + 1,
+            presentation.total_slides(),
+            step_counter
+        ),
+        height - 1,
+        Alignment::Center,
+        with_background(Style::fg(colors.accent).bold(), background),
         stdout,
-        presentation.current_theme().get_theme_colors().primary,
     );
-    for (i, line) in presentation
-        .current_slide()
-        .lines()
-        .skip_while(|line| line.trim().is_empty())
-        .enumerate()
-    {
-        let (line, color): (&str, Box<dyn Display>) = match line.starts_with("#") {
+    render_progress_bar(
+        presentation.current_step_position(),
+        presentation.total_steps(),
+        stdout,
+        with_background(Style::fg(colors.accent), background),
+    );
+}
+
+/// Renders the slide generated from the metadata: the title, subtitle, author
+/// and the date, event and location in one line.
+fn render_title_slide(
+    presentation: &Presentation,
+    area: Area,
+    stdout: &mut termion::raw::RawTerminal<std::io::Stdout>,
+) {
+    let metadata = &presentation.metadata;
+    let colors = presentation.current_theme().get_theme_colors();
+    let layout = metadata.title_slide_layout();
+    let width = match layout {
+        TitleSlideLayout::Left => (area.width as usize).saturating_sub(2),
+        _ => area.width as usize,
+    };
+    let text = |text: &str, style: Style| -> Vec<Line> {
+        text.lines()
+            .flat_map(|line| Line::new(vec![Span::new(line, style)]).wrap(width))
+            .collect()
+    };
+    let title = metadata.title.as_deref().unwrap_or_default();
+    let title_style = Style::fg(colors.primary).bold();
+    let large_title = presentation
+        .font
+        .as_ref()
+        .filter(|_| !title.contains('\n'))
+        .and_then(|font| font.render_fitting(title, width));
+    let mut lines = match large_title {
+        Some(rows) => rows
+            .into_iter()
+            .map(|row| Line::new(vec![Span::new(row, title_style)]))
+            .collect(),
+        None => text(title, title_style),
+    };
+    if let Some(subtitle) = &metadata.subtitle {
+        lines.push(Line::default());
+        lines.extend(text(
+            subtitle,
+            Style {
+                italic: true,
+                ..Style::fg(colors.secondary)
+            },
+        ));
+    }
+    if !metadata.authors.is_empty() {
+        lines.push(Line::default());
+        lines.extend(text(&metadata.authors.join(", "), Style::fg(colors.text)));
+    }
+    let details: Vec<&str> = [&metadata.date, &metadata.event, &metadata.location]
+        .into_iter()
+        .flatten()
+        .map(String::as_str)
+        .collect();
+    if !details.is_empty() {
+        lines.push(Line::default());
+        lines.extend(text(&details.join(" · "), Style::fg(colors.line_number)));
+    }
+
+    let rows = lines.len() as u16;
+    match layout {
+        TitleSlideLayout::Left => {
+            let bar = Span::new("▌ ", Style::fg(colors.accent));
+            let lines: Vec<Line> = lines
+                .into_iter()
+                .map(|line| line.prefixed(bar.clone()))
+                .collect();
+            let top = (area.height / 3).min(area.height.saturating_sub(rows));
+            let area = Area {
+                y: area.y + top,
+                height: area.height - top,
+                ..area
+            };
+            render_lines(&lines, area, colors.background, stdout);
+        }
+        _ => {
+            let top = VerticalAlignment::Center.offset(rows as usize, area.height as usize) as u16;
+            let area = Area {
+                y: area.y + top,
+                height: area.height - top,
+                ..area
+            };
+            render_aligned_lines(&lines, area, Alignment::Center, colors.background, stdout);
+        }
+    }
+}
+
+/// Shown instead of a slide when the terminal is below the minimum size.
+fn render_terminal_too_small(
+    width: u16,
+    height: u16,
+    stdout: &mut termion::raw::RawTerminal<std::io::Stdout>,
+) {
+    write!(
+        stdout,
+        "{}{}{}",
+        termion::clear::All,
+        graphics::clear_images(terminal::graphics_protocol()),
+        cursor::Hide
+    )
+    .unwrap();
+    let messages = [
+        String::from("Terminal too small"),
+        format!("{}x{}", width, height),
+        format!("needs {}x{}", layout::MIN_WIDTH, layout::MIN_HEIGHT),
+    ];
+    let top = (height / 2).saturating_sub(1).max(1);
+    for (row, message) in (top..=height).zip(&messages) {
+        let column = (width as usize).saturating_sub(display_width(message)) / 2;
+        write!(
+            stdout,
+            "{}{}",
+            cursor::Goto(column as u16 + 1, row),
+            Line::new(vec![Span::plain(message.as_str())]).truncate(width as usize)
+        )
+        .unwrap();
+    }
+    stdout.flush().unwrap();
+}
+
+/// Clears the screen, filling it with `background` if the theme has one.
+fn clear_screen(background: Option<Rgb>, stdout: &mut termion::raw::RawTerminal<std::io::Stdout>) {
+    if let Some(background) = background {
+        write!(stdout, "{}", color::Bg(background)).unwrap();
+    }
+    write!(
+        stdout,
+        "{}{}{}{}{}",
+        termion::clear::All,
+        graphics::clear_images(terminal::graphics_protocol()),
+        color::Bg(color::Reset),
+        cursor::Goto(1, 1),
+        cursor::Hide
+    )
+    .unwrap();
+}
+
+fn with_background(style: Style, background: Option<Rgb>) -> Style {
+    match background {
+        Some(background) => style.background(background),
+        None => style,
+    }
+}
+
+/// Writes `lines` into `area`, cutting off whatever does not fit.
+fn render_lines(
+    lines: &[Line],
+    area: Area,
+    background: Option<Rgb>,
+    stdout: &mut termion::raw::RawTerminal<std::io::Stdout>,
+) {
+    for (row, line) in (area.y..area.y + area.height).zip(lines) {
+        let mut line = line.clone().truncate(area.width as usize);
+        if let Some(background) = background {
+            line = line.with_background(background);
+        }
+        write!(stdout, "{}{}", cursor::Goto(area.x, row), line).unwrap();
+    }
+}
+
+/// Writes `lines` into `area` like `render_lines`, placing each line according
+/// to `alignment`.
+fn render_aligned_lines(
+    lines: &[Line],
+    area: Area,
+    alignment: Alignment,
+    background: Option<Rgb>,
+    stdout: &mut termion::raw::RawTerminal<std::io::Stdout>,
+) {
+    for (row, line) in (area.y..area.y + area.height).zip(lines) {
+        let offset = alignment.offset(line.width(), area.width as usize) as u16;
+        let area = Area {
+            x: area.x + offset,
+            y: row,
+            width: area.width - offset,
+            height: 1,
+        };
+        render_lines(std::slice::from_ref(line), area, background, stdout);
+    }
+}
+
+/// Writes `text` into `row` of the screen, placed according to `alignment`.
+fn render_aligned_text(
+    text: &str,
+    row: u16,
+    alignment: Alignment,
+    style: Style,
+    stdout: &mut termion::raw::RawTerminal<std::io::Stdout>,
+) {
+    let (width, _) = terminal_size().unwrap();
+    let area = Area {
+        x: 1,
+        y: row,
+        width,
+        height: 1,
+    };
+    let line = Line::new(vec![Span::new(text, style)]);
+    render_aligned_lines(&[line], area, alignment, None, stdout);
+}
+
+/// The grid of the overview in a terminal of the current size.
+pub fn overview_grid() -> Grid {
+    let (width, height) = terminal_size().unwrap();
+    let mut area = Area::slide_content(width, height, 1);
+    // Up to the footer, from just below the heading.
+    area.y = 3;
+    area.height = height.saturating_sub(4);
+    Grid::new(area)
+}
+
+/// Renders all slides as thumbnails of their first lines, with slide
+/// `selected` highlighted.
+pub fn render_overview(
+    presentation: &Presentation,
+    selected: usize,
+    stdout: &mut termion::raw::RawTerminal<std::io::Stdout>,
+) {
+    let theme = presentation.current_theme();
+    let colors = theme.get_theme_colors();
+    let background = colors.background;
+    clear_screen(background, stdout);
+    let (_, height) = terminal_size().unwrap();
+    render_aligned_text(
+        "Overview",
+        1,
+        Alignment::Center,
+        with_background(Style::fg(colors.primary).bold(), background),
+        stdout,
+    );
+    let grid = overview_grid();
+    let first_row = grid.first_row(selected);
+    for (index, slide) in presentation.slides.iter().enumerate() {
+        let Some(area) = grid.thumbnail(index, first_row) else {
+            continue;
+        };
+        let border = match index == selected {
+            true => Style::fg(colors.accent).bold(),
+            false => Style::fg(colors.line_number),
+        };
+        let inner_width = area.width.saturating_sub(2) as usize;
+        let content = match slide.is_title_slide {
             true => {
-                let (hash, line) = extract_prefix(line);
-                let header = Header::header_by_prefix(&hash).unwrap();
-                (
-                    line,
-                    Box::new(color::Fg(header.color(presentation.current_theme()))),
-                )
+                let metadata = &presentation.metadata;
+                [&metadata.title, &metadata.subtitle]
+                    .into_iter()
+                    .flatten()
+                    .zip([
+                        Style::fg(colors.primary).bold(),
+                        Style::fg(colors.secondary),
+                    ])
+                    .map(|(text, style)| Line::new(vec![Span::new(text.replace('\n', " "), style)]))
+                    .collect()
             }
-            _ => (line, Box::new(color::Fg(color::Reset))),
+            // The slide as it would be rendered, without large headings and
+            // images.
+            false => render_blocks(&slide.blocks, theme, None, Images::default(), inner_width),
         };
+        let number = format!("─ {} ", index + 1);
+        let mut lines = vec![Line::new(vec![Span::new(
+            format!(
+                "┌{}{}┐",
+                number,
+                "─".repeat(inner_width.saturating_sub(display_width(&number)))
+            ),
+            border,
+        )])];
+        let rows = (area.height as usize).saturating_sub(2);
+        let mut content = content.into_iter().filter(|line| line.width() > 0);
+        for _ in 0..rows {
+            let line = content.next().unwrap_or_default().truncate(inner_width);
+            let fill = inner_width.saturating_sub(line.width());
+            let mut row = line.prefixed(Span::new("│", border));
+            row.push(Span::plain(" ".repeat(fill)));
+            row.push(Span::new("│", border));
+            lines.push(row);
+        }
+        lines.push(Line::new(vec![Span::new(
+            format!("└{}┘", "─".repeat(inner_width)),
+            border,
+        )]));
+        render_lines(&lines, area, background, stdout);
+    }
+    render_aligned_text(
+        &format!("{}/{} slides", selected + 1, presentation.total_slides()),
+        height - 1,
+        Alignment::Center,
+        with_background(Style::fg(colors.accent).bold(), background),
+        stdout,
+    );
+    stdout.flush().unwrap();
+}
+
+/// Renders the presenter view: the speaker notes of the current slide next to
+/// a preview of the next slide, below a status line with the timing.
+pub fn render_presenter_view(
+    presentation: &Presentation,
+    elapsed: Duration,
+    slide_elapsed: Duration,
+    stdout: &mut termion::raw::RawTerminal<std::io::Stdout>,
+) {
+    let theme = presentation.current_theme();
+    let colors = theme.get_theme_colors();
+    let background = colors.background;
+    let (width, height) = terminal_size().unwrap();
+    clear_screen(background, stdout);
+    write!(stdout, "{}", cursor::Hide).unwrap();
+    render_presenter_clock(presentation, elapsed, slide_elapsed, stdout);
+    let separator = Span::new(
+        "─".repeat(width as usize),
+        with_background(Style::fg(colors.accent), background),
+    );
+    write!(stdout, "{}{}", cursor::Goto(1, 2), separator).unwrap();
+
+    let notes_area = Area {
+        x: 1,
+        y: 5,
+        width: (width * 3 / 5).saturating_sub(2),
+        height: height.saturating_sub(4),
+    };
+    let preview_area = Area {
+        x: notes_area.width + 4,
+        width: width.saturating_sub(notes_area.width + 3),
+        ..notes_area
+    };
+    let title_style = with_background(Style::fg(colors.primary).bold(), background);
+    write!(
+        stdout,
+        "{}{}{}{}",
+        cursor::Goto(notes_area.x, 3),
+        Span::new("Notes", title_style),
+        cursor::Goto(preview_area.x, 3),
+        Span::new("Next slide", title_style),
+    )
+    .unwrap();
+    let divider = Span::new(
+        "│",
+        with_background(Style::fg(colors.line_number), background),
+    );
+    for row in notes_area.y..height {
         write!(
             stdout,
-            "{}{}{}{}{}{}",
-            style::Bold,
-            cursor::Goto(1, i as u16 + 4),
-            color,
-            line,
-            color::Fg(color::Reset),
-            style::Reset
+            "{}{}",
+            cursor::Goto(notes_area.width + 2, row),
+            divider
         )
         .unwrap();
     }
-    render_text_centered(
-        format!(
-            "{}/{} slides",
-            presentation.current_slide + 1,
-            presentation.total_slides()
-        )
-        .as_str(),
-        true,
-        stdout,
-        presentation.current_theme().get_theme_colors().accent,
-    );
-    render_progress_bar(
-        presentation.current_slide,
+
+    let notes = presentation.current_slide().speaker_notes().join("\n\n");
+    let note_lines: Vec<Line> = notes
+        .lines()
+        .flat_map(|line| {
+            Line::new(vec![Span::new(line, Style::fg(colors.text))]).wrap(notes_area.width as usize)
+        })
+        .collect();
+    render_lines(&note_lines, notes_area, background, stdout);
+    let preview_lines = match presentation.next_slide() {
+        Some(slide) => render_blocks(
+            &slide.blocks,
+            theme,
+            presentation.font.as_ref(),
+            Images {
+                images: Some(&presentation.images),
+                cache: Some(&presentation.graphics),
+                max_rows: preview_area.height as usize,
+            },
+            preview_area.width as usize,
+        ),
+        None => vec![Line::new(vec![Span::new(
+            "End of presentation",
+            Style::fg(colors.line_number),
+        )])],
+    };
+    render_lines(&preview_lines, preview_area, background, stdout);
+    stdout.flush().unwrap();
+}
+
+/// Renders the status line of the presenter view with the position in the
+/// deck, the time since the presenter view started, the time spent on the
+/// slide against its budget and the current time.
+pub fn render_presenter_clock(
+    presentation: &Presentation,
+    elapsed: Duration,
+    slide_elapsed: Duration,
+    stdout: &mut termion::raw::RawTerminal<std::io::Stdout>,
+) {
+    let colors = presentation.current_theme().get_theme_colors();
+    let (width, _) = terminal_size().unwrap();
+    let position = format!(
+        "Slide {}/{} ({}/{})",
+        presentation.current_slide + 1,
         presentation.total_slides(),
-        stdout,
-        presentation.current_theme().get_theme_colors().accent,
+        presentation.current_step + 1,
+        presentation.current_slide().steps()
     );
+    let seconds = elapsed.as_secs();
+    let mut timing = Line::new(vec![Span::new(
+        format!(
+            "Elapsed {:02}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        ),
+        Style::fg(colors.accent).bold(),
+    )]);
+    let mut slide_time = format!("   Slide {}", format_minutes(slide_elapsed));
+    let mut slide_time_style = Style::fg(colors.text);
+    if let Some(budget) = presentation.current_slide().options.time_budget {
+        slide_time.push_str(&format!("/{}", format_minutes(budget)));
+        if slide_elapsed > budget {
+            slide_time.push_str(&format!(" +{}", format_minutes(slide_elapsed - budget)));
+            slide_time_style = Style::fg(colors.accent).bold();
+        }
+    }
+    timing.push(Span::new(slide_time, slide_time_style));
+    let clock = chrono::Local::now().format("%H:%M:%S").to_string();
+    let width = width as usize;
+    let timing_column = (width.saturating_sub(timing.width()) / 2).max(position.len() + 1);
+    let clock_column = width
+        .saturating_sub(clock.len())
+        .max(timing_column + timing.width() + 1);
+    write!(
+        stdout,
+        "{}{}{}{}{}{}{}{}",
+        cursor::Goto(1, 1),
+        termion::clear::CurrentLine,
+        Span::new(position, Style::fg(colors.secondary).bold()),
+        cursor::Goto(timing_column as u16 + 1, 1),
+        timing,
+        cursor::Goto(clock_column as u16 + 1, 1),
+        Span::new(clock, Style::fg(colors.text)),
+        cursor::Hide
+    )
+    .unwrap();
     stdout.flush().unwrap();
 }
 
-fn extract_prefix(s: &str) -> (String, &str) {
-    let prefix = s.chars().take_while(|c| *c == '#').collect::<String>();
-    let rest = s.trim_start_matches('#').trim_start();
-    (prefix, rest)
+fn format_minutes(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!("{:02}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// The decoded images of a presentation, the cache of their rendered lines
+/// and the rows an image may take up.
+#[derive(Clone, Copy, Default)]
+struct Images<'a> {
+    images: Option<&'a HashMap<String, Image>>,
+    cache: Option<&'a graphics::Cache>,
+    max_rows: usize,
+}
+
+/// Renders blocks into terminal lines, separating blocks by an empty line.
+/// Blocks after a column layout directive are placed side by side.
+fn render_blocks(
+    blocks: &[Block],
+    theme: &Theme,
+    font: Option<&Font>,
+    images: Images,
+    width: usize,
+) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut add_section = |section: Vec<Line>| {
+        if !section.is_empty() {
+            if !lines.is_empty() {
+                lines.push(Line::default());
+            }
+            lines.extend(section);
+        }
+    };
+    let is_layout = |block: &Block| matches!(block, Block::Directive(Directive::ColumnLayout(_)));
+    let mut rest = blocks;
+    loop {
+        let layout_start = rest.iter().position(is_layout).unwrap_or(rest.len());
+        let (stacked, columns) = rest.split_at(layout_start);
+        add_section(render_stacked_blocks(stacked, theme, font, images, width));
+        let Some((Block::Directive(Directive::ColumnLayout(ratios)), columns)) =
+            columns.split_first()
+        else {
+            break;
+        };
+        let layout_end = columns
+            .iter()
+            .position(|block| {
+                is_layout(block) || matches!(block, Block::Directive(Directive::ResetLayout))
+            })
+            .unwrap_or(columns.len());
+        add_section(render_columns(
+            ratios,
+            &columns[..layout_end],
+            theme,
+            font,
+            images,
+            width,
+        ));
+        rest = &columns[layout_end..];
+    }
+    lines
+}
+
+fn render_stacked_blocks(
+    blocks: &[Block],
+    theme: &Theme,
+    font: Option<&Font>,
+    images: Images,
+    width: usize,
+) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let blocks = blocks
+        .iter()
+        .filter(|block| !matches!(block, Block::Directive(_)));
+    for (i, block) in blocks.enumerate() {
+        if i > 0 {
+            lines.push(Line::default());
+        }
+        lines.extend(render_block(block, theme, font, images, width));
+    }
+    lines
+}
+
+/// Renders blocks into columns with the proportions `ratios`, each wrapped
+/// on its own. `<!-- column: n -->` selects the column of the blocks after it.
+fn render_columns(
+    ratios: &[u16],
+    blocks: &[Block],
+    theme: &Theme,
+    font: Option<&Font>,
+    images: Images,
+    width: usize,
+) -> Vec<Line> {
+    let widths = layout::split_columns(width, ratios, COLUMN_GAP);
+    let mut columns: Vec<Vec<Block>> = vec![Vec::new(); widths.len()];
+    let mut column = 0;
+    for block in blocks {
+        match block {
+            Block::Directive(Directive::Column(index)) => column = (*index).min(widths.len() - 1),
+            block => columns[column].push(block.clone()),
+        }
+    }
+    let columns: Vec<Vec<Line>> = columns
+        .iter()
+        .zip(&widths)
+        .map(|(blocks, width)| render_stacked_blocks(blocks, theme, font, images, *width))
+        .collect();
+    let height = columns.iter().map(Vec::len).max().unwrap_or(0);
+    (0..height)
+        .map(|row| {
+            let mut line = Line::default();
+            for (i, (column, width)) in columns.iter().zip(&widths).enumerate() {
+                if i > 0 {
+                    line.push(Span::plain(" ".repeat(COLUMN_GAP)));
+                }
+                let cell = column
+                    .get(row)
+                    .cloned()
+                    .unwrap_or_default()
+                    .truncate(*width);
+                // Pad every column so the columns stay in place when aligned.
+                let fill = width.saturating_sub(cell.width());
+                line.spans.extend(cell.spans);
+                line.push(Span::plain(" ".repeat(fill)));
+            }
+            line
+        })
+        .collect()
+}
+
+/// Renders a block, with level 1 headings in large letters if there is a
+/// `font` and they fit. Images missing from `images` are shown as their alt
+/// text.
+fn render_block(
+    block: &Block,
+    theme: &Theme,
+    font: Option<&Font>,
+    images: Images,
+    width: usize,
+) -> Vec<Line> {
+    match block {
+        Block::Heading { level, content } => {
+            let style = Header::header_by_level(*level).style(theme);
+            let large = font
+                .filter(|_| *level == 1)
+                .and_then(|font| font.render_fitting(&Inline::plain_text(content), width));
+            match large {
+                Some(rows) => rows
+                    .into_iter()
+                    .map(|row| Line::new(vec![Span::new(row, style)]))
+                    .collect(),
+                None => render_inlines(content, style, theme, width),
+            }
+        }
+        Block::Paragraph(content) => render_inlines(content, Style::default(), theme, width),
+        Block::List(list) => render_list(list, 0, theme, images, width),
+        Block::CodeBlock {
+            language,
+            code,
+            line_numbers,
+        } => render_code_block(language.as_deref(), code, *line_numbers, theme, width),
+        Block::BlockQuote { kind, blocks } => {
+            render_block_quote(*kind, blocks, theme, images, width)
+        }
+        Block::Table(table) => render_table(table, theme, width),
+        Block::Image { url, alt } => match images.images.and_then(|images| images.get(url)) {
+            Some(image) => render_image(url, image, images, width),
+            None => render_inlines(
+                &[Inline::Image {
+                    url: url.clone(),
+                    alt: alt.clone(),
+                }],
+                Style::default(),
+                theme,
+                width,
+            ),
+        },
+        Block::ThematicBreak => vec![Line::new(vec![Span::new(
+            "─".repeat(width),
+            Style::fg(theme.get_theme_colors().accent),
+        )])],
+        Block::Directive(_) => Vec::new(),
+    }
+}
+
+/// Renders an image as large as it fits into `width` columns and the rows of
+/// `images`.
+fn render_image(url: &str, image: &Image, images: Images, width: usize) -> Vec<Line> {
+    let (columns, rows) = graphics::fit(image, graphics::cell_size(), width, images.max_rows);
+    let protocol = terminal::graphics_protocol();
+    match images.cache {
+        Some(cache) => cache.render(url, image, protocol, columns, rows),
+        None => graphics::render(image, protocol, columns, rows),
+    }
+}
+
+/// Horizontal padding inside code blocks.
+const CODE_PADDING: usize = 2;
+
+/// Renders a code block on the theme's code background, with the language as
+/// a label in the top padding row.
+fn render_code_block(
+    language: Option<&str>,
+    code: &str,
+    line_numbers: bool,
+    theme: &Theme,
+    width: usize,
+) -> Vec<Line> {
+    let colors = theme.get_theme_colors();
+    let background = colors.code_background;
+    let mut lines = highlighting::highlight(code, language, &colors.syntax);
+    if line_numbers {
+        let number_width = lines.len().to_string().len();
+        lines = lines
+            .into_iter()
+            .enumerate()
+            .map(|(i, line)| {
+                line.prefixed(Span::new(
+                    format!("{:>number_width$} ", i + 1),
+                    Style::fg(colors.line_number),
+                ))
+            })
+            .collect();
+    }
+    let label = language.unwrap_or_default();
+    let content_width = lines
+        .iter()
+        .map(Line::width)
+        .chain(std::iter::once(display_width(label)))
+        .max()
+        .unwrap_or(0);
+    let block_width = (content_width + 2 * CODE_PADDING).min(width);
+    let inner_width = block_width.saturating_sub(2 * CODE_PADDING);
+    let padding = Span::plain(" ".repeat(CODE_PADDING));
+    let pad = |line: Line| {
+        let line = line.truncate(inner_width);
+        let fill = " ".repeat(inner_width.saturating_sub(line.width()));
+        let mut line = line.prefixed(padding.clone());
+        line.push(Span::plain(fill));
+        line.push(padding.clone());
+        line.with_background(background)
+    };
+    // Right-aligned by display width, which `{:>}` doesn't know about.
+    let label = Line::new(vec![Span::new(
+        " ".repeat(inner_width.saturating_sub(display_width(label))) + label,
+        Style::fg(colors.line_number),
+    )]);
+    std::iter::once(pad(label))
+        .chain(lines.into_iter().map(pad))
+        .chain(std::iter::once(pad(Line::default())))
+        .collect()
+}
+
+/// Bullet glyphs for unordered lists, cycled through by nesting depth.
+const BULLETS: [&str; 3] = ["•", "◦", "▪"];
+
+/// Renders a quote in italics behind a gutter bar, or a callout with its icon
+/// and title in the colour of its kind.
+fn render_block_quote(
+    kind: Option<Admonition>,
+    blocks: &[Block],
+    theme: &Theme,
+    images: Images,
+    width: usize,
+) -> Vec<Line> {
+    let colors = theme.get_theme_colors();
+    let mut lines = render_blocks(blocks, theme, None, images, width.saturating_sub(2));
+    let color = match kind {
+        None => {
+            for span in lines.iter_mut().flat_map(|line| line.spans.iter_mut()) {
+                span.style.italic = true;
+            }
+            colors.quote
+        }
+        Some(kind) => {
+            let (icon, title, color) = match kind {
+                Admonition::Note => ("ℹ", "Note", colors.note),
+                Admonition::Tip => ("★", "Tip", colors.tip),
+                Admonition::Important => ("‼", "Important", colors.important),
+                Admonition::Warning => ("⚠", "Warning", colors.warning),
+                Admonition::Caution => ("✖", "Caution", colors.caution),
+            };
+            let title = Line::new(vec![Span::new(
+                format!("{} {}", icon, title),
+                Style::fg(color).bold(),
+            )]);
+            lines.insert(0, title);
+            color
+        }
+    };
+    let gutter = Span::new("▌ ", Style::fg(color));
+    lines
+        .into_iter()
+        .map(|line| line.prefixed(gutter.clone()))
+        .collect()
+}
+
+/// Renders a table as a grid with box-drawing borders. Columns are narrowed
+/// to fit into `width`, wrapping their cells.
+fn render_table(table: &Table, theme: &Theme, width: usize) -> Vec<Line> {
+    let colors = theme.get_theme_colors();
+    let columns = table.alignments.len();
+    let header_style = Style::fg(colors.primary).bold();
+    let rows: Vec<Vec<Vec<Line>>> = std::iter::once((&table.header, header_style))
+        .chain(table.rows.iter().map(|row| (row, Style::default())))
+        .map(|(row, style)| {
+            (0..columns)
+                .map(|column| match row.get(column) {
+                    Some(cell) => inline_lines(cell, style, colors),
+                    None => Vec::new(),
+                })
+                .collect()
+        })
+        .collect();
+    let natural_widths: Vec<usize> = (0..columns)
+        .map(|column| {
+            rows.iter()
+                .flat_map(|row| row[column].iter().map(Line::width))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    // Each column has a border on its left and a space on both sides.
+    let available = width.saturating_sub(3 * columns + 1);
+    let widths = layout::fit_columns(&natural_widths, available);
+
+    let border_style = Style::fg(colors.line_number);
+    let border = |left: &str, middle: &str, right: &str| {
+        let segments: Vec<String> = widths.iter().map(|width| "─".repeat(width + 2)).collect();
+        Line::new(vec![Span::new(
+            format!("{}{}{}", left, segments.join(middle), right),
+            border_style,
+        )])
+    };
+    let mut lines = vec![border("┌", "┬", "┐")];
+    for (i, row) in rows.iter().enumerate() {
+        if i == 1 {
+            lines.push(border("├", "┼", "┤"));
+        }
+        let cells: Vec<Vec<Line>> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| cell.iter().flat_map(|line| line.wrap(*width)).collect())
+            .collect();
+        let height = cells.iter().map(Vec::len).max().unwrap_or(0).max(1);
+        for row_line in 0..height {
+            let mut line = Line::default();
+            for ((cell, width), alignment) in cells.iter().zip(&widths).zip(&table.alignments) {
+                line.push(Span::new("│ ", border_style));
+                let content = cell
+                    .get(row_line)
+                    .cloned()
+                    .unwrap_or_default()
+                    .truncate(*width);
+                let alignment = match alignment {
+                    ColumnAlignment::Center => Alignment::Center,
+                    ColumnAlignment::Right => Alignment::Right,
+                    ColumnAlignment::None | ColumnAlignment::Left => Alignment::Left,
+                };
+                let left = alignment.offset(content.width(), *width);
+                let right = width - left - content.width();
+                line.push(Span::plain(" ".repeat(left)));
+                line.spans.extend(content.spans);
+                line.push(Span::plain(" ".repeat(right + 1)));
+            }
+            line.push(Span::new("│", border_style));
+            lines.push(line);
+        }
+    }
+    lines.push(border("└", "┴", "┘"));
+    lines
+}
+
+fn render_list(
+    list: &List,
+    depth: usize,
+    theme: &Theme,
+    images: Images,
+    width: usize,
+) -> Vec<Line> {
+    let colors = theme.get_theme_colors();
+    let number_width = list.start.map_or(0, |start| {
+        (start + list.items.len() as u64 - 1).to_string().len()
+    });
+    let mut lines = Vec::new();
+    for (i, item) in list.items.iter().enumerate() {
+        let marker = match list.start {
+            Some(start) => Span::new(
+                format!("{:>number_width$}. ", start + i as u64),
+                Style::fg(colors.list_number).bold(),
+            ),
+            None => Span::new(
+                format!("{} ", BULLETS[depth % BULLETS.len()]),
+                Style::fg(colors.bullet),
+            ),
+        };
+        let indent = " ".repeat(marker.text.chars().count());
+        let item_width = width.saturating_sub(indent.len());
+        let item_lines = item.iter().flat_map(|block| match block {
+            Block::List(nested) => render_list(nested, depth + 1, theme, images, item_width),
+            block => render_block(block, theme, None, images, item_width),
+        });
+        for (j, line) in item_lines.enumerate() {
+            let prefix = if j == 0 {
+                marker.clone()
+            } else {
+                Span::plain(indent.clone())
+            };
+            lines.push(line.prefixed(prefix));
+        }
+    }
+    lines
+}
+
+/// Renders formatted text into lines no wider than `width`, with `style` as
+/// the style of unformatted text.
+fn render_inlines(inlines: &[Inline], style: Style, theme: &Theme, width: usize) -> Vec<Line> {
+    inline_lines(inlines, style, theme.get_theme_colors())
+        .iter()
+        .flat_map(|line| line.wrap(width))
+        .collect()
+}
+
+/// Turns inlines into styled spans, starting a new line at hard breaks.
+fn inline_lines(inlines: &[Inline], style: Style, colors: &ThemeColors) -> Vec<Line> {
+    let mut lines = vec![Line::default()];
+    let mut append = |new_lines: Vec<Line>| {
+        let mut new_lines = new_lines.into_iter();
+        if let Some(first) = new_lines.next() {
+            lines.last_mut().unwrap().spans.extend(first.spans);
+        }
+        lines.extend(new_lines);
+    };
+    for inline in inlines {
+        match inline {
+            Inline::Text(text) => append(vec![Line::new(vec![Span::new(text, style)])]),
+            Inline::Code(code) => append(vec![Line::new(vec![Span::new(
+                code,
+                Style {
+                    fg: Some(colors.accent),
+                    bg: Some(colors.code_background),
+                    ..style
+                },
+            )])]),
+            Inline::Emphasis(content) => append(inline_lines(
+                content,
+                Style {
+                    italic: true,
+                    ..style
+                },
+                colors,
+            )),
+            Inline::Strong(content) => append(inline_lines(content, style.bold(), colors)),
+            Inline::Strikethrough(content) => append(inline_lines(
+                content,
+                Style {
+                    strikethrough: true,
+                    ..style
+                },
+                colors,
+            )),
+            Inline::Link { url, content } => {
+                let link_style = Style {
+                    fg: Some(colors.secondary),
+                    underline: true,
+                    ..style
+                };
+                let mut link = inline_lines(content, link_style, colors);
+                if terminal::supports_hyperlinks() {
+                    for span in link.iter_mut().flat_map(|line| line.spans.iter_mut()) {
+                        span.link = Some(url.clone());
+                    }
+                } else if Inline::plain_text(content) != *url {
+                    link.last_mut().unwrap().push(Span::new(
+                        format!(" ({})", url),
+                        Style {
+                            fg: Some(colors.line_number),
+                            ..style
+                        },
+                    ));
+                }
+                append(link);
+            }
+            Inline::Image { alt, .. } => append(vec![Line::new(vec![Span::new(alt, style)])]),
+            Inline::SoftBreak => append(vec![Line::new(vec![Span::new(" ", style)])]),
+            Inline::HardBreak => append(vec![Line::default(), Line::default()]),
+        }
+    }
+    lines
+}
+
+/// Shows the prompt in the bottom row, over the progress bar, with the cursor
+/// after its text.
+pub fn render_prompt(
+    prompt: &Prompt,
+    stdout: &mut termion::raw::RawTerminal<std::io::Stdout>,
+    color: Rgb,
+    background: Option<Rgb>,
+) {
+    let (_, height) = terminal_size().unwrap();
+    if let Some(background) = background {
+        write!(stdout, "{}", color::Bg(background)).unwrap();
+    }
+    write!(
+        stdout,
+        "{}{}{}{}{}",
+        cursor::Goto(1, height),
+        termion::clear::CurrentLine,
+        color::Bg(color::Reset),
+        Span::new(
+            format!("{}{}", prompt.symbol, prompt.text),
+            with_background(Style::fg(color), background)
+        ),
+        cursor::Show
+    )
+    .unwrap();
+    stdout.flush().unwrap();
 }
 
 pub async fn render_notification(
     text: &str,
     stdout: &mut termion::raw::RawTerminal<std::io::Stdout>,
     color: Rgb,
+    background: Option<Rgb>,
 ) {
     let (width, _) = terminal_size().unwrap();
-    let start = width - text.len() as u16;
+    let start = width.saturating_sub(display_width(text) as u16).max(1);
     write!(
         stdout,
-        "{}{}{}{}{}",
+        "{}{}{}",
         cursor::Goto(start, 1),
-        color::Fg(color),
-        text,
-        color::Fg(color::Reset),
+        Span::new(text, with_background(Style::fg(color), background)),
         cursor::Hide
     )
     .unwrap();
     stdout.flush().unwrap();
     tokio::spawn(async move {
-        clear_notification(start, 3).await;
+        clear_notification(start, 3, background).await;
     });
 }
 
-pub async fn clear_notification(start: u16, delay_seconds: i8) {
+pub async fn clear_notification(start: u16, delay_seconds: i8, background: Option<Rgb>) {
     thread::sleep(Duration::from_secs(delay_seconds as u64));
     let mut stdout = stdout().into_raw_mode().unwrap();
+    if let Some(background) = background {
+        write!(stdout, "{}", color::Bg(background)).unwrap();
+    }
     write!(
         stdout,
-        "{}{}{}",
+        "{}{}{}{}",
         cursor::Goto(start, 1),
         termion::clear::UntilNewline,
+        color::Bg(color::Reset),
         cursor::Hide
     )
     .unwrap();
     stdout.flush().unwrap();
 }
 
-fn render_text_centered(
-    text: &str,
-    goto_bottom: bool,
-    stdout: &mut termion::raw::RawTerminal<std::io::Stdout>,
-    color: Rgb,
-) {
-    let (width, height) = terminal_size().unwrap();
-    let padding = (width as usize - text.len()) / 2;
-    let spaces = " ".repeat(padding);
-    let (_, y) = stdout.cursor_pos().unwrap();
-    let y_position = if goto_bottom { height - 1 } else { y };
-    write!(
-        stdout,
-        "{}{}{}{}{}{}{}",
-        cursor::Goto(1, y_position),
-        style::Bold,
-        color::Fg(color),
-        spaces,
-        text,
-        color::Fg(color::Reset),
-        style::Reset
-    )
-    .unwrap();
-}
-
 fn render_progress_bar(
-    current_slide: usize,
-    total_slides: usize,
+    current_step: usize,
+    total_steps: usize,
     stdout: &mut termion::raw::RawTerminal<std::io::Stdout>,
-    color: Rgb,
+    style: Style,
 ) {
     let (width, height) = terminal_size().unwrap();
-    let progress_ratio = current_slide.add(1) as f32 / total_slides as f32;
+    let progress_ratio = current_step.add(1) as f32 / total_steps as f32;
     let progress_length = (progress_ratio * width as f32) as usize;
+    let rest = Style { fg: None, ..style };
     write!(
         stdout,
         "{}{}{}{}",
         cursor::Goto(1, height),
-        color::Fg(color),
-        "".repeat(progress_length),
-        color::Fg(color::Reset)
-    )
-    .unwrap();
-
-    write!(
-        stdout,
-        "{}{}",
-        " ".repeat(width as usize - progress_length),
+        Span::new("".repeat(progress_length), style),
+        Span::new(" ".repeat(width as usize - progress_length), rest),
         cursor::Goto(1, height + 1)
     )
     .unwrap();
@@ -204,32 +1256,123 @@ fn render_progress_bar(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::markdown::parse_blocks;
+
+    /// The text of each line, without styles.
+    fn text(lines: &[Line]) -> Vec<String> {
+        lines
+            .iter()
+            .map(|line| line.spans.iter().map(|span| span.text.as_str()).collect())
+            .collect()
+    }
 
     #[test]
-    fn test_extract_hash_no_hash() {
-        let (prefix, rest) = extract_prefix("Hello, world!");
-        assert_eq!(prefix, "");
-        assert_eq!(rest, "Hello, world!");
+    fn test_render_columns_with_skewed_ratios() {
+        let theme = &Theme::built_in()[0];
+        let blocks = parse_blocks("<!-- column: 0 -->\nLeft\n<!-- column: 1 -->\nRight");
+        let lines = render_columns(&[1, 100], &blocks, theme, None, Images::default(), 76);
+        // The narrow column still gets a column, wrapping its text there.
+        assert_eq!(text(&lines)[0], format!("L  Right{}", " ".repeat(68)));
+        assert_eq!(text(&lines)[1], format!("e{}", " ".repeat(75)));
+        assert_eq!(lines.len(), 4);
+        assert!(lines.iter().all(|line| line.width() == 76));
     }
 
     #[test]
-    fn test_extract_hash_one_hash() {
-        let (prefix, rest) = extract_prefix("#Hello, world!");
-        assert_eq!(prefix, "#");
-        assert_eq!(rest, "Hello, world!");
+    fn test_render_code_block_in_a_narrow_column() {
+        let theme = &Theme::built_in()[0];
+        let blocks = parse_blocks("<!-- column: 0 -->\n```rust\nfn main() {}\n```");
+        let lines = render_columns(&[1, 20], &blocks, theme, None, Images::default(), 76);
+        assert_eq!(lines.len(), 3);
+        assert!(lines.iter().all(|line| line.width() == 76));
     }
 
     #[test]
-    fn test_extract_hash_multiple_hashes() {
-        let (prefix, rest) = extract_prefix("###Hello, world!");
-        assert_eq!(prefix, "###");
-        assert_eq!(rest, "Hello, world!");
+    fn test_render_nested_lists() {
+        let theme = &Theme::built_in()[0];
+        let blocks =
+            parse_blocks("9. A first item that wraps\n   - nested\n     - deeper\n10. second");
+        let lines = render_blocks(&blocks, theme, None, Images::default(), 16);
+        // Numbers are right-aligned and wrapped lines hang under the text.
+        assert_eq!(
+            text(&lines),
+            vec![
+                " 9. A first item",
+                "    that wraps",
+                "    ◦ nested",
+                "      ▪ deeper",
+                "10. second",
+            ]
+        );
+        let colors = theme.get_theme_colors();
+        assert_eq!(
+            lines[0].spans[0].style,
+            Style::fg(colors.list_number).bold()
+        );
+        assert_eq!(lines[2].spans[1].style, Style::fg(colors.bullet));
     }
 
     #[test]
-    fn test_remove_leading_whitespaces_from_rest() {
-        let (prefix, rest) = extract_prefix("###  Hello, world!");
-        assert_eq!(prefix, "###");
-        assert_eq!(rest, "Hello, world!");
+    fn test_render_table() {
+        let theme = &Theme::built_in()[0];
+        let blocks = parse_blocks("| Name | Count |\n|:--|--:|\n| a | **1** |\n| bb | 22 |");
+        let lines = render_blocks(&blocks, theme, None, Images::default(), 40);
+        assert_eq!(
+            text(&lines),
+            vec![
+                "┌──────┬───────┐",
+                "│ Name │ Count │",
+                "├──────┼───────┤",
+                "│ a    │     1 │",
+                "│ bb   │    22 │",
+                "└──────┴───────┘",
+            ]
+        );
+        let one = lines[3].spans.iter().find(|span| span.text == "1").unwrap();
+        assert!(one.style.bold);
+
+        // Too wide for the area, the wider column wraps.
+        let blocks = parse_blocks("| Left | Right |\n|--|--|\n| a long cell | short |");
+        let lines = render_blocks(&blocks, theme, None, Images::default(), 17);
+        assert_eq!(
+            text(&lines),
+            vec![
+                "┌───────┬───────┐",
+                "│ Left  │ Right │",
+                "├───────┼───────┤",
+                "│ a     │ short │",
+                "│ long  │       │",
+                "│ cell  │       │",
+                "└───────┴───────┘",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_quotes_and_callouts() {
+        let theme = &Theme::built_in()[0];
+        let colors = theme.get_theme_colors();
+        let blocks = parse_blocks("> Quoted text");
+        let lines = render_blocks(&blocks, theme, None, Images::default(), 40);
+        assert_eq!(text(&lines), vec!["▌ Quoted text"]);
+        assert_eq!(lines[0].spans[0].style, Style::fg(colors.quote));
+        assert!(lines[0].spans[1].style.italic);
+
+        for (kind, title, color) in [
+            ("NOTE", "ℹ Note", colors.note),
+            ("TIP", "★ Tip", colors.tip),
+            ("WARNING", "⚠ Warning", colors.warning),
+            ("CAUTION", "✖ Caution", colors.caution),
+        ] {
+            let blocks = parse_blocks(&format!("> [!{}]\n> Careful", kind));
+            let lines = render_blocks(&blocks, theme, None, Images::default(), 40);
+            assert_eq!(
+                text(&lines),
+                vec![format!("▌ {}", title), String::from("▌ Careful")]
+            );
+            assert_eq!(lines[0].spans[0].style, Style::fg(color));
+            assert_eq!(lines[0].spans[1].style, Style::fg(color).bold());
+            assert!(!lines[1].spans[1].style.italic);
+        }
     }
 }
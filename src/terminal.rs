@@ -0,0 +1,139 @@
+use std::{env, sync::OnceLock};
+
+/// Whether the terminal turns OSC 8 escape sequences into clickable links.
+/// `TERM_DECK_HYPERLINKS=0` or `=1` overrides the detection.
+pub fn supports_hyperlinks() -> bool {
+    static SUPPORTED: OnceLock<bool> = OnceLock::new();
+    *SUPPORTED.get_or_init(|| detect_hyperlinks(|name| env::var(name).ok()))
+}
+
+fn detect_hyperlinks(var: impl Fn(&str) -> Option<String>) -> bool {
+    if let Some(setting) = var("TERM_DECK_HYPERLINKS") {
+        return setting != "0";
+    }
+    let term_program = var("TERM_PROGRAM").unwrap_or_default();
+    let term = var("TERM").unwrap_or_default();
+    matches!(
+        term_program.as_str(),
+        "iTerm.app" | "WezTerm" | "vscode" | "ghostty" | "Hyper"
+    ) || ["kitty", "alacritty", "foot", "wezterm", "ghostty"]
+        .iter()
+        .any(|name| term.contains(name))
+        || var("KITTY_WINDOW_ID").is_some()
+        || var("WT_SESSION").is_some()
+        // VTE based terminals like GNOME Terminal support links since 0.50.
+        || var("VTE_VERSION")
+            .and_then(|version| version.parse::<u32>().ok())
+            .is_some_and(|version| version >= 5000)
+}
+
+/// How images are drawn in the terminal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GraphicsProtocol {
+    Kitty,
+    Iterm2,
+    Sixel,
+    /// Coloured `▀` characters, two pixels per cell.
+    HalfBlocks,
+    /// Characters of increasing density, for terminals without colours.
+    Ascii,
+}
+
+impl GraphicsProtocol {
+    fn parse(value: &str) -> Option<GraphicsProtocol> {
+        match value {
+            "kitty" => Some(GraphicsProtocol::Kitty),
+            "iterm2" => Some(GraphicsProtocol::Iterm2),
+            "sixel" => Some(GraphicsProtocol::Sixel),
+            "blocks" => Some(GraphicsProtocol::HalfBlocks),
+            "ascii" => Some(GraphicsProtocol::Ascii),
+            _ => None,
+        }
+    }
+}
+
+/// The best way of drawing images the terminal supports. `TERM_DECK_IMAGES`
+/// set to `kitty`, `iterm2`, `sixel`, `blocks` or `ascii` overrides the
+/// detection.
+pub fn graphics_protocol() -> GraphicsProtocol {
+    static PROTOCOL: OnceLock<GraphicsProtocol> = OnceLock::new();
+    *PROTOCOL.get_or_init(|| detect_graphics(|name| env::var(name).ok()))
+}
+
+fn detect_graphics(var: impl Fn(&str) -> Option<String>) -> GraphicsProtocol {
+    if let Some(protocol) =
+        var("TERM_DECK_IMAGES").and_then(|value| GraphicsProtocol::parse(&value))
+    {
+        return protocol;
+    }
+    // Asking the terminal would race with the key reader for stdin, so the
+    // environment has to do. Multiplexers need their own escape sequences.
+    if var("TMUX").is_some() || var("STY").is_some() {
+        return GraphicsProtocol::HalfBlocks;
+    }
+    let term_program = var("TERM_PROGRAM").unwrap_or_default();
+    let term = var("TERM").unwrap_or_default();
+    if term.contains("kitty") || term.contains("ghostty") || var("KITTY_WINDOW_ID").is_some() {
+        GraphicsProtocol::Kitty
+    } else if matches!(term_program.as_str(), "iTerm.app" | "WezTerm") {
+        GraphicsProtocol::Iterm2
+    } else if ["foot", "mlterm", "yaft", "contour"]
+        .iter()
+        .any(|name| term.contains(name))
+        || var("WT_SESSION").is_some()
+    {
+        GraphicsProtocol::Sixel
+    } else if term == "linux" || term == "dumb" {
+        GraphicsProtocol::Ascii
+    } else {
+        GraphicsProtocol::HalfBlocks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn var<'a>(vars: &'a [(&str, &str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        |name| {
+            vars.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| String::from(*value))
+        }
+    }
+
+    fn detect(vars: &[(&str, &str)]) -> bool {
+        detect_hyperlinks(var(vars))
+    }
+
+    #[test]
+    fn test_detect_hyperlinks() {
+        assert!(detect(&[("TERM_PROGRAM", "iTerm.app")]));
+        assert!(detect(&[("TERM", "xterm-kitty")]));
+        assert!(detect(&[("VTE_VERSION", "6800")]));
+        assert!(!detect(&[("TERM", "xterm-256color")]));
+        assert!(!detect(&[
+            ("TERM", "xterm-kitty"),
+            ("TERM_DECK_HYPERLINKS", "0")
+        ]));
+    }
+
+    #[test]
+    fn test_detect_graphics() {
+        let detect = |vars: &[(&str, &str)]| detect_graphics(var(vars));
+        assert_eq!(detect(&[("TERM", "xterm-kitty")]), GraphicsProtocol::Kitty);
+        assert_eq!(
+            detect(&[("TERM_PROGRAM", "iTerm.app")]),
+            GraphicsProtocol::Iterm2
+        );
+        assert_eq!(detect(&[("TERM", "foot")]), GraphicsProtocol::Sixel);
+        assert_eq!(
+            detect(&[("TERM", "xterm-kitty"), ("TMUX", "/tmp/tmux")]),
+            GraphicsProtocol::HalfBlocks
+        );
+        assert_eq!(
+            detect(&[("TERM", "foot"), ("TERM_DECK_IMAGES", "ascii")]),
+            GraphicsProtocol::Ascii
+        );
+    }
+}
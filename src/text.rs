@@ -0,0 +1,467 @@
+use std::{
+    fmt::{self, Display},
+    sync::Arc,
+};
+use termion::{
+    color::{self, Rgb},
+    style,
+};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// The number of terminal columns `text` takes up.
+pub fn display_width(text: &str) -> usize {
+    text.width()
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Style {
+    pub fg: Option<Rgb>,
+    pub bg: Option<Rgb>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub strikethrough: bool,
+}
+
+impl Style {
+    pub fn fg(color: Rgb) -> Style {
+        Style {
+            fg: Some(color),
+            ..Style::default()
+        }
+    }
+
+    pub fn bold(self) -> Style {
+        Style { bold: true, ..self }
+    }
+
+    pub fn background(self, color: Rgb) -> Style {
+        Style {
+            bg: Some(color),
+            ..self
+        }
+    }
+}
+
+/// A piece of text rendered with a single style.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub text: String,
+    pub style: Style,
+    /// A URL the text links to as an OSC 8 terminal hyperlink.
+    pub link: Option<String>,
+    /// The escape sequence of an image drawn over the cells of the text,
+    /// which is only there for its width.
+    pub graphic: Option<Arc<str>>,
+}
+
+impl Span {
+    pub fn new(text: impl Into<String>, style: Style) -> Span {
+        Span {
+            text: text.into(),
+            style,
+            link: None,
+            graphic: None,
+        }
+    }
+
+    pub fn plain(text: impl Into<String>) -> Span {
+        Span::new(text, Style::default())
+    }
+
+    /// A span of `width` cells covered by an image. `sequence` draws the image
+    /// and is empty in the rows below its top row.
+    pub fn graphic(sequence: Arc<str>, width: usize) -> Span {
+        Span {
+            graphic: Some(sequence),
+            ..Span::plain(" ".repeat(width))
+        }
+    }
+
+    /// A span with the same style and link but different text.
+    fn with_text(&self, text: &str) -> Span {
+        Span {
+            text: String::from(text),
+            ..self.clone()
+        }
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(sequence) = &self.graphic {
+            // The image is drawn from the cursor, which is then moved past
+            // the cells so that nothing is written over the image.
+            return write!(
+                f,
+                "\x1b7{}\x1b8\x1b[{}C",
+                sequence,
+                display_width(&self.text)
+            );
+        }
+        if self.style.bold {
+            write!(f, "{}", style::Bold)?;
+        }
+        if self.style.italic {
+            write!(f, "{}", style::Italic)?;
+        }
+        if self.style.underline {
+            write!(f, "{}", style::Underline)?;
+        }
+        if self.style.strikethrough {
+            write!(f, "{}", style::CrossedOut)?;
+        }
+        if let Some(fg) = self.style.fg {
+            write!(f, "{}", color::Fg(fg))?;
+        }
+        if let Some(bg) = self.style.bg {
+            write!(f, "{}", color::Bg(bg))?;
+        }
+        match &self.link {
+            Some(url) => write!(f, "\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, self.text)?,
+            None => write!(f, "{}", self.text)?,
+        }
+        write!(
+            f,
+            "{}{}{}",
+            color::Fg(color::Reset),
+            color::Bg(color::Reset),
+            style::Reset
+        )
+    }
+}
+
+/// A single terminal row made up of styled spans.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Line {
+    pub spans: Vec<Span>,
+}
+
+impl Line {
+    pub fn new(spans: Vec<Span>) -> Line {
+        Line { spans }
+    }
+
+    pub fn push(&mut self, span: Span) {
+        self.spans.push(span);
+    }
+
+    /// Returns the line with `prefix` put in front of it.
+    pub fn prefixed(mut self, prefix: Span) -> Line {
+        self.spans.insert(0, prefix);
+        self
+    }
+
+    /// The number of terminal columns the line takes up.
+    pub fn width(&self) -> usize {
+        self.spans
+            .iter()
+            .map(|span| display_width(&span.text))
+            .sum()
+    }
+
+    /// Cuts the line to at most `width` columns, marking the cut with `…`.
+    pub fn truncate(mut self, width: usize) -> Line {
+        if self.width() <= width {
+            return self;
+        }
+        if width == 0 {
+            return Line::default();
+        }
+        let (head, _) = Word {
+            spans: self.spans,
+            is_space: false,
+        }
+        .split_at(width.saturating_sub(1));
+        self.spans = head.spans;
+        let style = self.spans.last().map(|span| span.style).unwrap_or_default();
+        self.spans.push(Span::new("…", style));
+        self
+    }
+
+    /// Sets the background of every span without one.
+    pub fn with_background(mut self, color: Rgb) -> Line {
+        for span in &mut self.spans {
+            span.style.bg = span.style.bg.or(Some(color));
+        }
+        self
+    }
+
+    /// Gives the parts of the line matching `query`, ignoring case, the
+    /// colours of `style`.
+    pub fn highlight(self, query: &str, style: Style) -> Line {
+        let fold = |c: char| c.to_lowercase().next().unwrap_or(c);
+        let query: Vec<char> = query.chars().map(fold).collect();
+        // Images only have spaces, which shouldn't match.
+        let text: Vec<Option<char>> = self
+            .spans
+            .iter()
+            .flat_map(|span| {
+                let searchable = span.graphic.is_none();
+                span.text.chars().map(move |c| searchable.then(|| fold(c)))
+            })
+            .collect();
+        let mut matched = vec![false; text.len()];
+        let mut start = 0;
+        while !query.is_empty() && start + query.len() <= text.len() {
+            let found = text[start..start + query.len()]
+                .iter()
+                .zip(&query)
+                .all(|(c, expected)| *c == Some(*expected));
+            match found {
+                true => {
+                    matched[start..start + query.len()].fill(true);
+                    start += query.len();
+                }
+                false => start += 1,
+            }
+        }
+        let mut matched = matched.into_iter();
+        let mut spans = Vec::new();
+        for span in self.spans {
+            let mut piece = String::new();
+            let mut piece_matched = false;
+            for c in span.text.chars() {
+                let is_match = matched.next().unwrap_or(false);
+                if is_match != piece_matched && !piece.is_empty() {
+                    spans.push(highlighted(&span, &piece, piece_matched, style));
+                    piece.clear();
+                }
+                piece.push(c);
+                piece_matched = is_match;
+            }
+            if !piece.is_empty() {
+                spans.push(highlighted(&span, &piece, piece_matched, style));
+            }
+        }
+        Line::new(spans)
+    }
+
+    /// Breaks the line into lines no wider than `width`, preferably at
+    /// whitespace. Words longer than `width` are split.
+    pub fn wrap(&self, width: usize) -> Vec<Line> {
+        let width = width.max(1);
+        let mut lines = vec![Line::default()];
+        let mut line_width = 0;
+        let mut pending_space: Option<Word> = None;
+        for word in words(&self.spans) {
+            if word.is_space {
+                if line_width > 0 {
+                    pending_space = Some(word);
+                }
+                continue;
+            }
+            let space_width = pending_space.as_ref().map_or(0, |space| space.width());
+            if line_width > 0 && line_width + space_width + word.width() > width {
+                lines.push(Line::default());
+                line_width = 0;
+                pending_space = None;
+            }
+            if let Some(space) = pending_space.take() {
+                line_width += space.width();
+                lines.last_mut().unwrap().spans.extend(space.spans);
+            }
+            let mut word = word;
+            while line_width + word.width() > width {
+                let (head, tail) = word.split_at(width - line_width);
+                if head.spans.is_empty() {
+                    // A single character wider than the line, let it overflow.
+                    word = tail;
+                    break;
+                }
+                lines.last_mut().unwrap().spans.extend(head.spans);
+                lines.push(Line::default());
+                line_width = 0;
+                word = tail;
+            }
+            line_width += word.width();
+            lines.last_mut().unwrap().spans.extend(word.spans);
+        }
+        lines
+    }
+}
+
+/// A run of either whitespace or non-whitespace characters, possibly
+/// spanning several styles.
+struct Word {
+    spans: Vec<Span>,
+    is_space: bool,
+}
+
+impl Word {
+    fn width(&self) -> usize {
+        self.spans
+            .iter()
+            .map(|span| display_width(&span.text))
+            .sum()
+    }
+
+    /// Splits the word after `at` columns. Characters are never split, so the
+    /// head may be narrower.
+    fn split_at(self, at: usize) -> (Word, Word) {
+        let mut head = Vec::new();
+        let mut tail = Vec::new();
+        let mut remaining = at;
+        for span in self.spans {
+            if !tail.is_empty() {
+                tail.push(span);
+                continue;
+            }
+            let width = display_width(&span.text);
+            if width <= remaining {
+                remaining -= width;
+                head.push(span);
+                continue;
+            }
+            let mut index = span.text.len();
+            for (i, c) in span.text.char_indices() {
+                let width = c.width().unwrap_or(0);
+                if width > remaining {
+                    index = i;
+                    break;
+                }
+                remaining -= width;
+            }
+            if index > 0 {
+                head.push(span.with_text(&span.text[..index]));
+            }
+            tail.push(span.with_text(&span.text[index..]));
+        }
+        let is_space = self.is_space;
+        (
+            Word {
+                spans: head,
+                is_space,
+            },
+            Word {
+                spans: tail,
+                is_space,
+            },
+        )
+    }
+}
+
+fn words(spans: &[Span]) -> Vec<Word> {
+    let mut words: Vec<Word> = Vec::new();
+    for span in spans {
+        let mut rest = span.text.as_str();
+        while let Some(first) = rest.chars().next() {
+            let is_space = first.is_whitespace();
+            let end = rest
+                .find(|c: char| c.is_whitespace() != is_space)
+                .unwrap_or(rest.len());
+            let piece = span.with_text(&rest[..end]);
+            match words.last_mut() {
+                Some(word) if word.is_space == is_space => word.spans.push(piece),
+                _ => words.push(Word {
+                    spans: vec![piece],
+                    is_space,
+                }),
+            }
+            rest = &rest[end..];
+        }
+    }
+    words
+}
+
+impl Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for span in &self.spans {
+            write!(f, "{}", span)?;
+        }
+        Ok(())
+    }
+}
+
+/// A piece of `span`, in the colours of `style` if `matched`.
+fn highlighted(span: &Span, text: &str, matched: bool, style: Style) -> Span {
+    let mut piece = span.with_text(text);
+    if matched {
+        piece.style.fg = style.fg;
+        piece.style.bg = style.bg;
+    }
+    piece
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(lines: &[Line]) -> Vec<String> {
+        lines
+            .iter()
+            .map(|line| line.spans.iter().map(|span| span.text.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_wrap_at_whitespace() {
+        let line = Line::new(vec![Span::plain("the quick brown fox")]);
+        assert_eq!(texts(&line.wrap(10)), vec!["the quick", "brown fox"]);
+    }
+
+    #[test]
+    fn test_wrap_keeps_words_across_spans_together() {
+        let line = Line::new(vec![
+            Span::plain("aaaa "),
+            Span::new("bold", Style::default().bold()),
+            Span::plain(", c"),
+        ]);
+        assert_eq!(texts(&line.wrap(8)), vec!["aaaa", "bold, c"]);
+    }
+
+    #[test]
+    fn test_wrap_splits_long_words() {
+        let line = Line::new(vec![Span::plain("abcdefghij")]);
+        assert_eq!(texts(&line.wrap(4)), vec!["abcd", "efgh", "ij"]);
+    }
+
+    #[test]
+    fn test_wrap_by_display_width() {
+        let line = Line::new(vec![Span::plain("日本語 テキスト")]);
+        assert_eq!(texts(&line.wrap(7)), vec!["日本語", "テキス", "ト"]);
+    }
+
+    #[test]
+    fn test_truncate() {
+        let line = Line::new(vec![Span::plain("abc"), Span::plain("def")]);
+        assert_eq!(texts(&[line.clone().truncate(4)]), vec!["abc…"]);
+        assert_eq!(texts(&[line.clone().truncate(6)]), vec!["abcdef"]);
+        assert_eq!(line.truncate(0), Line::default());
+    }
+
+    #[test]
+    fn test_wrap_keeps_links() {
+        let mut span = Span::plain("see the docs");
+        span.link = Some(String::from("https://example.com"));
+        let line = Line::new(vec![span]);
+        let lines = line.wrap(8);
+        assert_eq!(texts(&lines), vec!["see the", "docs"]);
+        assert_eq!(
+            lines[1].spans[0].link.as_deref(),
+            Some("https://example.com")
+        );
+        assert!(lines[1]
+            .to_string()
+            .starts_with("\x1b]8;;https://example.com\x1b\\docs\x1b]8;;\x1b\\"));
+    }
+
+    #[test]
+    fn test_highlight_ignores_case_across_spans() {
+        let style = Style::fg(Rgb(0, 0, 0)).background(Rgb(255, 0, 0));
+        let line = Line::new(vec![Span::plain("Rust is"), Span::plain(" rusty")]);
+        let highlighted = line.highlight("RUST", style);
+        assert_eq!(
+            texts(std::slice::from_ref(&highlighted)),
+            vec!["Rust is rusty"]
+        );
+        let matched: Vec<&str> = highlighted
+            .spans
+            .iter()
+            .filter(|span| span.style.bg.is_some())
+            .map(|span| span.text.as_str())
+            .collect();
+        assert_eq!(matched, vec!["Rust", "rust"]);
+        assert_eq!(highlighted.spans.len(), 5);
+    }
+}